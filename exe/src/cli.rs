@@ -0,0 +1,666 @@
+use std::path::PathBuf;
+
+use my_trash_bin_240704_lib::via::MAX_UNORDERED_WAYPOINTS;
+
+use crate::{data::Objective, timetable::Time};
+
+pub const USAGE: &str = "\
+Usage: my-trash-bin-240704-exe [--data <path>] <command> [arguments]
+
+Commands:
+  route <from> <to> [--alternatives <n> | --pareto | --depart <HH:MM>]
+                            Find the shortest route between two stations, the <n>
+                            shortest different ones, every route that no other
+                            beats in distance, transfers and stops at once, or the
+                            one arriving first when leaving at <HH:MM>
+  route <from> <to> --via <station> [--via <station> ...] [--any-order]
+                            Find the shortest route passing the --via stations in the
+                            order given, or in the best order with --any-order
+  stations [--line <line>]  List stations, optionally only those on one line
+  lines                     List lines and their station counts
+  info <station>            Show ids, lines and neighbours of a station
+  search <query>            List stations matching a query, best matches first
+  batch <file> [--threads <n>]
+                            Answer one \"<from>,<to>\" query per line of <file> (- for
+                            stdin) in parallel, printing CSV
+  distances <from>          List every reachable station with its distance from <from>,
+                            nearest first
+  table [--threads <n>]     Print the distance in km between every pair of stations
+                            as a CSV matrix
+  export <dot|graphml|geojson> [<from> <to>]
+                            Print the routing graph as GraphViz DOT or GraphML, or
+                            stations and lines as GeoJSON, with the route from <from>
+                            to <to> highlighted
+  changes [--threads <n>]   List the routes whose cost --scenario changes, those it makes
+                            impossible first, then by decreasing change
+  validate                  Check the station data for broken links, duplicate ids,
+                            one-way transfers, unreachable stations and misplaced
+                            coordinates, failing if any error is found
+  help                      Show this message
+
+Stations can be given by id (e.g. 5호선_020), by full or partial name, by
+initial consonants (e.g. ㄱㄴ for 강남) or romanized (e.g. gangnam).
+
+Options:
+  --data <path>             Path to the station data (default: data.json)
+  --gtfs <path>             Load a GTFS feed, as a directory or zip archive, instead
+                            of --data
+  --transfer-penalty <km>   Extra cost in km for every change of lines (default: 3)
+  --optimize <distance|stops|transfers|time>
+                            What routes minimise: distance with the transfer penalty,
+                            stops or transfers with distance breaking ties, or travel
+                            time at 34 km/h with trains every 6 minutes (default:
+                            distance); not valid with --pareto or --depart
+  --timetable <path>        Service of lines for --depart, as JSON mapping line names
+                            to {\"headwayMinutes\", \"speedKmh\", \"firstDeparture\",
+                            \"lastDeparture\"}, each optional (default: every 6
+                            minutes at 34 km/h from 05:30 to 24:00)
+  --avoid-station <station> Route around a closed station, which trains pass without
+                            stopping; may be repeated (route and distances)
+  --avoid-line <line>       Route around a suspended line; may be repeated (route and
+                            distances)
+  --via <station>           Station a route has to pass, riding through or changing
+                            lines there; may be repeated (route)
+  --any-order               Pass the --via stations in whichever order is shortest,
+                            for at most 12 of them
+  --scenario <path>         Apply disruptions to the data, as JSON with optional
+                            \"closedStations\" (station ids), \"closedSegments\"
+                            ({\"line\", \"from\", \"to\"} between adjacent stations) and
+                            \"shuttles\" ({\"from\", \"to\"} with an optional \"name\")";
+
+const DEFAULT_DATA_PATH: &str = "data.json";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExportFormat {
+    Dot,
+    GraphMl,
+    GeoJson,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Route {
+        from: String,
+        to: String,
+        alternatives: Option<usize>,
+        pareto: bool,
+        depart: Option<Time>,
+        /// stations to pass, in order unless `any_order`
+        via: Vec<String>,
+        any_order: bool,
+    },
+    Stations {
+        line: Option<String>,
+    },
+    Lines,
+    Info {
+        station: String,
+    },
+    Search {
+        query: String,
+    },
+    Batch {
+        input: String,
+        threads: Option<usize>,
+    },
+    Distances {
+        from: String,
+    },
+    Table {
+        threads: Option<usize>,
+    },
+    Export {
+        format: ExportFormat,
+        route: Option<(String, String)>,
+    },
+    Changes {
+        threads: Option<usize>,
+    },
+    Validate,
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Cli {
+    pub data: PathBuf,
+    pub gtfs: Option<PathBuf>,
+    pub transfer_penalty: Option<f32>,
+    pub optimize: Option<Objective>,
+    pub timetable: Option<PathBuf>,
+    pub avoid_stations: Vec<String>,
+    pub avoid_lines: Vec<String>,
+    pub scenario: Option<PathBuf>,
+    pub command: Command,
+}
+
+/// Whether `command` searches the routing graph, whose weights --transfer-penalty and
+/// --optimize set
+fn uses_weights(command: &Command) -> bool {
+    match command {
+        Command::Route { pareto, depart, .. } => !pareto && depart.is_none(),
+        Command::Batch { .. }
+        | Command::Distances { .. }
+        | Command::Table { .. }
+        | Command::Changes { .. } => true,
+        Command::Export { route, .. } => route.is_some(),
+        Command::Stations { .. }
+        | Command::Lines
+        | Command::Info { .. }
+        | Command::Search { .. }
+        | Command::Validate
+        | Command::Help => false,
+    }
+}
+
+fn option_value(name: &str, args: &mut impl Iterator<Item = String>) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("Option {} requires a value", name))
+}
+
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Cli, String> {
+    let mut args = args.into_iter();
+    let mut data = None;
+    let mut gtfs = None;
+    let mut line = None;
+    let mut transfer_penalty = None;
+    let mut optimize = None;
+    let mut threads = None;
+    let mut alternatives = None;
+    let mut pareto = false;
+    let mut depart = None;
+    let mut timetable = None;
+    let mut avoid_stations = Vec::new();
+    let mut avoid_lines = Vec::new();
+    let mut scenario = None;
+    let mut via = Vec::new();
+    let mut any_order = false;
+    let mut positional = Vec::new();
+    let mut help = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--data" => data = Some(PathBuf::from(option_value(&arg, &mut args)?)),
+            "--gtfs" => gtfs = Some(PathBuf::from(option_value(&arg, &mut args)?)),
+            "--line" => line = Some(option_value(&arg, &mut args)?),
+            "--transfer-penalty" => {
+                let value = option_value(&arg, &mut args)?;
+                transfer_penalty = Some(
+                    value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|value| value.is_finite() && *value >= 0.0)
+                        .ok_or_else(|| format!("Invalid transfer penalty: {}", value))?,
+                );
+            }
+            "--optimize" => {
+                let value = option_value(&arg, &mut args)?;
+                optimize = Some(
+                    Objective::parse(&value)
+                        .ok_or_else(|| format!("Unknown objective: {}", value))?,
+                );
+            }
+            "--threads" => {
+                let value = option_value(&arg, &mut args)?;
+                threads = Some(
+                    value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|threads| *threads > 0)
+                        .ok_or_else(|| format!("Invalid thread count: {}", value))?,
+                );
+            }
+            "--alternatives" => {
+                let value = option_value(&arg, &mut args)?;
+                alternatives = Some(
+                    value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|alternatives| *alternatives > 0)
+                        .ok_or_else(|| format!("Invalid number of alternatives: {}", value))?,
+                );
+            }
+            "--pareto" => pareto = true,
+            "--depart" => {
+                let value = option_value(&arg, &mut args)?;
+                depart = Some(
+                    Time::parse(&value)
+                        .ok_or_else(|| format!("Invalid departure time: {}", value))?,
+                );
+            }
+            "--timetable" => timetable = Some(PathBuf::from(option_value(&arg, &mut args)?)),
+            "--avoid-station" => avoid_stations.push(option_value(&arg, &mut args)?),
+            "--avoid-line" => avoid_lines.push(option_value(&arg, &mut args)?),
+            "--via" => via.push(option_value(&arg, &mut args)?),
+            "--any-order" => any_order = true,
+            "--scenario" => scenario = Some(PathBuf::from(option_value(&arg, &mut args)?)),
+            "-h" | "--help" => help = true,
+            _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+            _ => positional.push(arg),
+        }
+    }
+
+    if data.is_some() && gtfs.is_some() {
+        return Err("Options --data and --gtfs cannot be combined".to_string());
+    }
+    let data = data.unwrap_or_else(|| PathBuf::from(DEFAULT_DATA_PATH));
+
+    if help {
+        return Ok(Cli {
+            data,
+            gtfs,
+            transfer_penalty,
+            optimize,
+            timetable,
+            avoid_stations,
+            avoid_lines,
+            scenario,
+            command: Command::Help,
+        });
+    }
+
+    let mut positional = positional.into_iter();
+    let command = match positional.next().as_deref() {
+        None | Some("help") => Command::Help,
+        Some("route") => {
+            let from = positional.next().ok_or("route requires <from> and <to>")?;
+            let to = positional.next().ok_or("route requires <from> and <to>")?;
+            Command::Route {
+                from,
+                to,
+                alternatives: alternatives.take(),
+                pareto: std::mem::take(&mut pareto),
+                depart: depart.take(),
+                via: std::mem::take(&mut via),
+                any_order: std::mem::take(&mut any_order),
+            }
+        }
+        Some("stations") => Command::Stations { line: line.take() },
+        Some("lines") => Command::Lines,
+        Some("info") => Command::Info {
+            station: positional.next().ok_or("info requires <station>")?,
+        },
+        Some("search") => Command::Search {
+            query: positional.next().ok_or("search requires <query>")?,
+        },
+        Some("batch") => Command::Batch {
+            input: positional.next().ok_or("batch requires <file>")?,
+            threads: threads.take(),
+        },
+        Some("distances") => Command::Distances {
+            from: positional.next().ok_or("distances requires <from>")?,
+        },
+        Some("table") => Command::Table {
+            threads: threads.take(),
+        },
+        Some("export") => {
+            let format = match positional.next().as_deref() {
+                Some("dot") => ExportFormat::Dot,
+                Some("graphml") => ExportFormat::GraphMl,
+                Some("geojson") => ExportFormat::GeoJson,
+                Some(format) => return Err(format!("Unknown export format: {}", format)),
+                None => return Err("export requires <dot|graphml|geojson>".to_string()),
+            };
+            let route = match (positional.next(), positional.next()) {
+                (Some(from), Some(to)) => Some((from, to)),
+                (None, None) => None,
+                _ => return Err("export requires both <from> and <to>".to_string()),
+            };
+            if route.is_some() && format != ExportFormat::GeoJson {
+                return Err("Only geojson exports can highlight a route".to_string());
+            }
+            Command::Export { format, route }
+        }
+        Some("changes") => Command::Changes {
+            threads: threads.take(),
+        },
+        Some("validate") => Command::Validate,
+        Some(command) => return Err(format!("Unknown command: {}", command)),
+    };
+
+    if let Some(extra) = positional.next() {
+        return Err(format!("Unexpected argument: {}", extra));
+    }
+    if line.is_some() {
+        return Err("Option --line is only valid for stations".to_string());
+    }
+    if let Command::Route {
+        alternatives,
+        pareto,
+        depart,
+        via,
+        any_order,
+        ..
+    } = &command
+    {
+        if !via.is_empty() {
+            if alternatives.is_some() || *pareto || depart.is_some() {
+                return Err(
+                    "Option --via cannot be combined with --alternatives, --pareto or --depart"
+                        .to_string(),
+                );
+            }
+            if !avoid_stations.is_empty() || !avoid_lines.is_empty() {
+                return Err(
+                    "Option --via cannot be combined with --avoid-station or --avoid-line"
+                        .to_string(),
+                );
+            }
+            if *any_order && via.len() > MAX_UNORDERED_WAYPOINTS {
+                return Err(format!(
+                    "Option --any-order accepts at most {} --via stations",
+                    MAX_UNORDERED_WAYPOINTS
+                ));
+            }
+        } else if *any_order {
+            return Err("Option --any-order requires --via".to_string());
+        }
+        if [alternatives.is_some(), *pareto, depart.is_some()]
+            .into_iter()
+            .filter(|given| *given)
+            .count()
+            > 1
+        {
+            return Err(
+                "Options --alternatives, --pareto and --depart cannot be combined".to_string(),
+            );
+        }
+        if optimize.is_some() && (*pareto || depart.is_some()) {
+            return Err(
+                "Option --optimize cannot be combined with --pareto or --depart".to_string(),
+            );
+        }
+        if transfer_penalty.is_some() && (*pareto || depart.is_some()) {
+            return Err(
+                "Option --transfer-penalty cannot be combined with --pareto or --depart"
+                    .to_string(),
+            );
+        }
+        if timetable.is_some() && depart.is_none() {
+            return Err("Option --timetable requires --depart".to_string());
+        }
+    } else if timetable.is_some() {
+        return Err("Option --timetable is only valid for route".to_string());
+    }
+    if depart.is_some() {
+        return Err("Option --depart is only valid for route".to_string());
+    }
+    if pareto {
+        return Err("Option --pareto is only valid for route".to_string());
+    }
+    if alternatives.is_some() {
+        return Err("Option --alternatives is only valid for route".to_string());
+    }
+    if !via.is_empty() {
+        return Err("Option --via is only valid for route".to_string());
+    }
+    if any_order {
+        return Err("Option --any-order is only valid for route".to_string());
+    }
+    if threads.is_some() {
+        return Err("Option --threads is only valid for batch, table and changes".to_string());
+    }
+    for (given, option) in [
+        (transfer_penalty.is_some(), "--transfer-penalty"),
+        (optimize.is_some(), "--optimize"),
+    ] {
+        if given && !uses_weights(&command) {
+            return Err(format!(
+                "Option {} is only valid for route, batch, distances, table, changes and \
+                 export with a route",
+                option
+            ));
+        }
+    }
+    if matches!(command, Command::Changes { .. }) && scenario.is_none() {
+        return Err("changes requires --scenario".to_string());
+    }
+    if !avoid_stations.is_empty() || !avoid_lines.is_empty() {
+        match &command {
+            Command::Route {
+                pareto: false,
+                depart: None,
+                ..
+            }
+            | Command::Distances { .. } => {}
+            Command::Route { .. } => {
+                return Err(
+                    "Options --avoid-station and --avoid-line cannot be combined with --pareto \
+                     or --depart"
+                        .to_string(),
+                )
+            }
+            _ => {
+                return Err(
+                    "Options --avoid-station and --avoid-line are only valid for route and \
+                     distances"
+                        .to_string(),
+                )
+            }
+        }
+    }
+
+    Ok(Cli {
+        data,
+        gtfs,
+        transfer_penalty,
+        optimize,
+        timetable,
+        avoid_stations,
+        avoid_lines,
+        scenario,
+        command,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Cli, String> {
+        parse_args(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn defaults() {
+        let cli = parse("route 잠실 강남").unwrap();
+        assert_eq!(
+            cli,
+            Cli {
+                data: PathBuf::from(DEFAULT_DATA_PATH),
+                gtfs: None,
+                transfer_penalty: None,
+                optimize: None,
+                timetable: None,
+                avoid_stations: Vec::new(),
+                avoid_lines: Vec::new(),
+                scenario: None,
+                command: Command::Route {
+                    from: "잠실".to_string(),
+                    to: "강남".to_string(),
+                    alternatives: None,
+                    pareto: false,
+                    depart: None,
+                    via: Vec::new(),
+                    any_order: false,
+                },
+            }
+        );
+        assert_eq!(parse("").unwrap().command, Command::Help);
+        assert_eq!(parse("help").unwrap().command, Command::Help);
+        // --help wins over anything invalid after it
+        assert_eq!(parse("--help route").unwrap().command, Command::Help);
+    }
+
+    #[test]
+    fn options() {
+        let cli = parse("--data other.json stations --line 2호선").unwrap();
+        assert_eq!(cli.data, PathBuf::from("other.json"));
+        assert_eq!(
+            cli.command,
+            Command::Stations {
+                line: Some("2호선".to_string())
+            }
+        );
+        assert_eq!(parse("lines").unwrap().command, Command::Lines);
+        assert_eq!(
+            parse("info 잠실").unwrap().command,
+            Command::Info {
+                station: "잠실".to_string()
+            }
+        );
+        assert_eq!(
+            parse("search 강남").unwrap().command,
+            Command::Search {
+                query: "강남".to_string()
+            }
+        );
+        assert_eq!(
+            parse("--transfer-penalty 1.5 route a b")
+                .unwrap()
+                .transfer_penalty,
+            Some(1.5)
+        );
+        assert_eq!(
+            parse("--optimize stops route a b").unwrap().optimize,
+            Some(Objective::Stops)
+        );
+        for args in [
+            "batch q.csv",
+            "distances a",
+            "table",
+            "export geojson a b",
+            "--scenario s.json changes",
+        ] {
+            let cli = parse(&format!("--optimize time --transfer-penalty 1 {}", args)).unwrap();
+            assert_eq!(cli.optimize, Some(Objective::Time), "{}", args);
+        }
+        assert_eq!(
+            parse("batch queries.csv --threads 4").unwrap().command,
+            Command::Batch {
+                input: "queries.csv".to_string(),
+                threads: Some(4)
+            }
+        );
+        assert_eq!(
+            parse("table").unwrap().command,
+            Command::Table { threads: None }
+        );
+        assert_eq!(
+            parse("distances 잠실").unwrap().command,
+            Command::Distances {
+                from: "잠실".to_string()
+            }
+        );
+        assert!(matches!(
+            parse("route a b --alternatives 3").unwrap().command,
+            Command::Route {
+                alternatives: Some(3),
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse("route a b --pareto").unwrap().command,
+            Command::Route { pareto: true, .. }
+        ));
+        let cli = parse("route a b --depart 08:30 --timetable service.json").unwrap();
+        assert_eq!(cli.timetable, Some(PathBuf::from("service.json")));
+        assert!(matches!(
+            cli.command,
+            Command::Route {
+                depart: Some(_),
+                ..
+            }
+        ));
+        let cli = parse("route a b --via c --via d --any-order").unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Route { via, any_order: true, .. } if via == ["c", "d"]
+        ));
+        let cli = parse("--gtfs feed.zip lines").unwrap();
+        assert_eq!(cli.data, PathBuf::from(DEFAULT_DATA_PATH));
+        assert_eq!(cli.gtfs, Some(PathBuf::from("feed.zip")));
+        assert_eq!(
+            parse("export dot").unwrap().command,
+            Command::Export {
+                format: ExportFormat::Dot,
+                route: None,
+            }
+        );
+        assert_eq!(
+            parse("export geojson a b").unwrap().command,
+            Command::Export {
+                format: ExportFormat::GeoJson,
+                route: Some(("a".to_string(), "b".to_string())),
+            }
+        );
+        assert_eq!(parse("validate").unwrap().command, Command::Validate);
+        let cli = parse("--avoid-station 서울역 --avoid-line 2호선 --avoid-line 3호선 distances a")
+            .unwrap();
+        assert_eq!(cli.avoid_stations, vec!["서울역"]);
+        assert_eq!(cli.avoid_lines, vec!["2호선", "3호선"]);
+        let cli = parse("--scenario s.json changes --threads 2").unwrap();
+        assert_eq!(cli.scenario, Some(PathBuf::from("s.json")));
+        assert_eq!(cli.command, Command::Changes { threads: Some(2) });
+    }
+
+    #[test]
+    fn errors() {
+        let too_many = (0..=MAX_UNORDERED_WAYPOINTS)
+            .map(|i| format!("--via s{}", i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let too_many = format!("route a b --any-order {}", too_many);
+        let cases = [
+// malformed input
+("--bogus lines", "Unknown option: --bogus"),
+("fly", "Unknown command: fly"),
+("lines extra", "Unexpected argument: extra"),
+("stations --line", "Option --line requires a value"),
+("route a b --via", "Option --via requires a value"),
+("--transfer-penalty -1 lines", "Invalid transfer penalty: -1"),
+("batch b --threads 0", "Invalid thread count: 0"),
+("route a b --alternatives 0", "Invalid number of alternatives: 0"),
+("route a b --depart noon", "Invalid departure time: noon"),
+("--optimize fast route a b", "Unknown objective: fast"),
+("export png", "Unknown export format: png"),
+// missing arguments
+("route a", "route requires <from> and <to>"),
+("info", "info requires <station>"),
+("search", "search requires <query>"),
+("batch", "batch requires <file>"),
+("distances", "distances requires <from>"),
+("export", "export requires <dot|graphml|geojson>"),
+("export geojson a", "export requires both <from> and <to>"),
+("changes", "changes requires --scenario"),
+// conflicts
+("--data a --gtfs b lines", "Options --data and --gtfs cannot be combined"),
+("export dot a b", "Only geojson exports can highlight a route"),
+("lines --line 2호선", "Option --line is only valid for stations"),
+("route a b --alternatives 2 --pareto", "Options --alternatives, --pareto and --depart cannot be combined"),
+("route a b --pareto --depart 08:00", "Options --alternatives, --pareto and --depart cannot be combined"),
+("--optimize time route a b --pareto", "Option --optimize cannot be combined with --pareto or --depart"),
+("--transfer-penalty 1 route a b --depart 08:00", "Option --transfer-penalty cannot be combined with --pareto or --depart"),
+("--transfer-penalty 1 lines", "Option --transfer-penalty is only valid for route, batch, distances, table, changes and export with a route"),
+("--optimize stops info a", "Option --optimize is only valid for route, batch, distances, table, changes and export with a route"),
+("--optimize time validate", "Option --optimize is only valid for route, batch, distances, table, changes and export with a route"),
+("--transfer-penalty 1 export dot", "Option --transfer-penalty is only valid for route, batch, distances, table, changes and export with a route"),
+("route a b --timetable t.json", "Option --timetable requires --depart"),
+("lines --timetable t.json", "Option --timetable is only valid for route"),
+("lines --depart 08:00", "Option --depart is only valid for route"),
+("lines --pareto", "Option --pareto is only valid for route"),
+("lines --alternatives 2", "Option --alternatives is only valid for route"),
+("route a b --via c --alternatives 2", "Option --via cannot be combined with --alternatives, --pareto or --depart"),
+("route a b --via c --avoid-line 2호선", "Option --via cannot be combined with --avoid-station or --avoid-line"),
+(too_many.as_str(), "Option --any-order accepts at most 12 --via stations"),
+("route a b --any-order", "Option --any-order requires --via"),
+("distances a --via b", "Option --via is only valid for route"),
+("distances a --any-order", "Option --any-order is only valid for route"),
+("route a b --threads 2", "Option --threads is only valid for batch, table and changes"),
+("route a b --pareto --avoid-station c", "Options --avoid-station and --avoid-line cannot be combined with --pareto or --depart"),
+("table --avoid-line 2호선", "Options --avoid-station and --avoid-line are only valid for route and distances"),
+];
+        for (args, expected) in cases {
+            assert_eq!(parse(args), Err(expected.to_string()), "{}", args);
+        }
+    }
+}
@@ -1,33 +1,45 @@
-use std::{
-    cell::RefCell,
-    collections::HashMap,
-    error::Error,
-    rc::{Rc, Weak},
+use std::{collections::HashMap, error::Error, fmt, sync::Arc};
+
+use my_trash_bin_240704_lib::graph::{
+    Graph, GraphBuilder, GraphDistance, GraphDistanceF32, GraphEdge, NodeId,
 };
 
-use my_trash_bin_240704_lib::graph::{Graph, GraphDistanceF32};
+use crate::timetable::{DEFAULT_HEADWAY_MINUTES, DEFAULT_SPEED_KMH, DWELL_TIME, TRANSFER_TIME};
+
+pub mod gtfs;
+pub mod lookup;
+pub mod scenario;
+pub mod validate;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StationRaw {
-    id: String,                          /* 역 식별자 */
-    name: String,                        /* 역명 */
-    line: String,                        /* 노선 */
-    next_station_id: Option<String>,     /* 하행 방향 다음 역 */
+    id: String,   /* 역 식별자 */
+    name: String, /* 역명 */
+    line: String, /* 노선 */
+    #[serde(default)]
+    line_name: Option<String>, /* 노선명, 노선 식별자와 다를 때 */
+    next_station_id: Option<String>, /* 하행 방향 다음 역 */
     previous_station_id: Option<String>, /* 상행 방향 다음 역 */
-    transfer_station_ids: Vec<String>,   /* 환승역 목록, 식별자랑 노선이 다른 동명의 식별자 목록 */
-    latitude: f32,                       /* 위도 */
-    longitude: f32,                      /* 경도 */
+    transfer_station_ids: Vec<String>, /* 환승역 목록, 식별자랑 노선이 다른 동명의 식별자 목록 */
+    latitude: f32, /* 위도 */
+    longitude: f32, /* 경도 */
+}
+
+/// Stations and lines as loaded, linked to each other by index and never changed afterwards
+struct Network {
+    stations: Vec<StationInternal>,
+    lines: Vec<LineInternal>,
 }
 
+/// Neighbours of a station on one line, as indices into [`Network::stations`]
 #[derive(Debug)]
 pub struct StationLine {
-    next_station: Option<Weak<RefCell<StationInternal>>>,
-    previous_station: Option<Weak<RefCell<StationInternal>>>,
-    line: Weak<RefCell<LineInternal>>,
+    next_station: Option<usize>,
+    previous_station: Option<usize>,
+    line: usize,
 }
 
-#[derive(Debug)]
 struct StationInternal {
     ids: Vec<String>,
     name: String,
@@ -36,45 +48,394 @@ struct StationInternal {
     longitude: f32,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct Station {
-    internal: Rc<RefCell<StationInternal>>,
+    network: Arc<Network>,
+    index: usize,
 }
 
 impl PartialEq for Station {
     fn eq(&self, other: &Self) -> bool {
-        self.internal.as_ptr() == other.internal.as_ptr()
+        Arc::ptr_eq(&self.network, &other.network) && self.index == other.index
     }
 }
 
 impl Eq for Station {}
 
+impl fmt::Debug for Station {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Station")
+            .field("id", &self.id())
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
 impl Station {
+    fn internal(&self) -> &StationInternal {
+        &self.network.stations[self.index]
+    }
+
+    fn station(&self, index: usize) -> Station {
+        Station {
+            network: self.network.clone(),
+            index,
+        }
+    }
+
     pub fn name(&self) -> String {
-        self.internal.borrow().name.clone()
+        self.internal().name.clone()
     }
 
-    pub fn has_id(&self, id: &String) -> bool {
-        self.internal.borrow().ids.contains(id)
+    pub fn id(&self) -> String {
+        self.internal().ids[0].clone()
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        self.internal().ids.clone()
+    }
+
+    pub fn coordinates(&self) -> (f32, f32) {
+        let internal = self.internal();
+        (internal.latitude, internal.longitude)
+    }
+
+    /// Great-circle distance in km to `other`
+    pub fn distance_to(&self, other: &Station) -> f32 {
+        let (a_latitude, a_longitude) = self.coordinates();
+        let (b_latitude, b_longitude) = other.coordinates();
+        distance(a_latitude, a_longitude, b_latitude, b_longitude)
+    }
+
+    /// The adjacent station on `line` in `direction`
+    pub fn neighbor(&self, line: &Line, direction: Direction) -> Option<Station> {
+        let station_line = self.internal().lines.get(&line.id())?;
+        match direction {
+            Direction::Up => station_line.previous_station,
+            Direction::Down => station_line.next_station,
+        }
+        .map(|index| self.station(index))
+    }
+
+    /// (line, previous station, next station) for every line serving this station
+    pub fn lines(&self) -> Vec<(Line, Option<Station>, Option<Station>)> {
+        let mut result = self
+            .internal()
+            .lines
+            .values()
+            .map(|station_line| {
+                (
+                    Line {
+                        network: self.network.clone(),
+                        index: station_line.line,
+                    },
+                    station_line
+                        .previous_station
+                        .map(|index| self.station(index)),
+                    station_line.next_station.map(|index| self.station(index)),
+                )
+            })
+            .collect::<Vec<_>>();
+        result.sort_by_key(|(line, _, _)| (line.name(), line.id()));
+        result
     }
 }
 
-#[derive(Debug)]
 struct LineInternal {
+    id: String,
     name: String,
-    stations: Vec<Station>,
+    /// indices into [`Network::stations`]
+    stations: Vec<usize>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct Line {
-    internal: Rc<RefCell<LineInternal>>,
+    network: Arc<Network>,
+    index: usize,
+}
+
+impl fmt::Debug for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Line").field("id", &self.id()).finish()
+    }
+}
+
+impl Line {
+    fn internal(&self) -> &LineInternal {
+        &self.network.lines[self.index]
+    }
+
+    /// The `line` of its stations in the input
+    pub fn id(&self) -> String {
+        self.internal().id.clone()
+    }
+
+    pub fn name(&self) -> String {
+        self.internal().name.clone()
+    }
+
+    pub fn stations(&self) -> Vec<Station> {
+        self.internal()
+            .stations
+            .iter()
+            .map(|&index| Station {
+                network: self.network.clone(),
+                index,
+            })
+            .collect()
+    }
+
+    /// Stations visited in `direction` from every end of the line; a circular line is run once
+    /// around from its first station
+    pub fn runs(&self, direction: Direction) -> Vec<Vec<Station>> {
+        let opposite = match direction {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        };
+        let stations = self.stations();
+        let mut starts = stations
+            .iter()
+            .filter(|station| station.neighbor(self, opposite).is_none())
+            .cloned()
+            .collect::<Vec<_>>();
+        if starts.is_empty() {
+            starts.extend(stations.first().cloned());
+        }
+        starts
+            .into_iter()
+            .map(|start| {
+                let mut run = vec![start.clone()];
+                let mut current = start;
+                while let Some(next) = current.neighbor(self, direction) {
+                    let closes_loop = run.contains(&next);
+                    run.push(next.clone());
+                    if closes_loop {
+                        break;
+                    }
+                    current = next;
+                }
+                run
+            })
+            .filter(|run| run.len() > 1)
+            .collect()
+    }
+
+    /// Last station reached from `from` in `direction`, or `None` on a circular line
+    pub fn terminal(&self, from: &Station, direction: Direction) -> Option<Station> {
+        let mut current = from.clone();
+        while let Some(next) = current.neighbor(self, direction) {
+            if next == *from {
+                return None;
+            }
+            current = next;
+        }
+        Some(current)
+    }
+}
+
+impl PartialEq for Line {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.network, &other.network) && self.index == other.index
+    }
+}
+
+impl Eq for Line {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RouteNodeKind {
+    /// where every route from the station starts
+    Entrance,
+    /// where every route to the station ends
+    Exit,
+    /// boarding the line at the station toward one direction, so that turning back is a transfer
+    Platform(Line, Direction),
+}
+
+/// Node of the routing graph; changing between two platforms of a station is a transfer
+#[derive(Clone, Debug)]
+pub struct RouteNode {
+    pub station: Station,
+    pub kind: RouteNodeKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// towards `previous_station_id` (상행)
+    Up,
+    /// towards `next_station_id` (하행)
+    Down,
+}
+
+/// Payload of a routing graph edge
+#[derive(Clone, Debug, PartialEq)]
+pub enum Hop {
+    Board(Line),
+    Ride { line: Line, direction: Direction },
+    Transfer { from: Line, to: Line },
+    Alight(Line),
+}
+
+/// Cost in km added for every change of lines
+pub const DEFAULT_TRANSFER_PENALTY: f32 = 3.0;
+
+/// Cost of a stop or transfer when it is what an objective minimises, far above the
+/// distance of any route so that distance only breaks ties
+const PRIMARY_COST: f32 = 1000.0;
+
+/// What the routing graph weighs its edges by
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Objective {
+    /// km ridden, plus the transfer penalty
+    #[default]
+    Distance,
+    /// stations passed, then distance
+    Stops,
+    /// changes of lines, then distance
+    Transfers,
+    /// minutes at the default speed and headway, including waits and walks
+    Time,
+}
+
+impl Objective {
+    pub fn parse(value: &str) -> Option<Objective> {
+        match value {
+            "distance" => Some(Objective::Distance),
+            "stops" => Some(Objective::Stops),
+            "transfers" => Some(Objective::Transfers),
+            "time" => Some(Objective::Time),
+            _ => None,
+        }
+    }
+
+    /// Route `cost` under this objective in its own units, with the stops or transfers that
+    /// come first split from the distance that breaks ties
+    pub fn format_cost(self, cost: f32) -> String {
+        let counted = |unit: &str| {
+            let count = (cost / PRIMARY_COST).floor();
+            format!("{} {} + {:.1} km", count, unit, cost - count * PRIMARY_COST)
+        };
+        match self {
+            Objective::Distance => format!("{:.1} km", cost),
+            Objective::Stops => counted("stops"),
+            Objective::Transfers => counted("transfers"),
+            Objective::Time => format!("{:.1} min", cost),
+        }
+    }
+
+    /// Never below `km`, so the straight-line distance stays an admissible heuristic
+    fn ride(self, km: f32) -> f32 {
+        match self {
+            Objective::Distance | Objective::Transfers => km,
+            Objective::Stops => PRIMARY_COST + km,
+            Objective::Time => km / DEFAULT_SPEED_KMH * 60.0 + DWELL_TIME as f32 / 60.0,
+        }
+    }
+
+    fn board(self) -> f32 {
+        match self {
+            Objective::Time => DEFAULT_HEADWAY_MINUTES / 2.0,
+            _ => 0.0,
+        }
+    }
+
+    fn transfer(self, transfer_penalty: f32) -> f32 {
+        match self {
+            Objective::Distance | Objective::Stops => transfer_penalty,
+            Objective::Transfers => PRIMARY_COST + transfer_penalty,
+            Objective::Time => TRANSFER_TIME as f32 / 60.0 + DEFAULT_HEADWAY_MINUTES / 2.0,
+        }
+    }
+}
+
+pub struct RoutingOptions {
+    pub transfer_penalty: f32,
+    pub objective: Objective,
+}
+
+impl Default for RoutingOptions {
+    fn default() -> Self {
+        RoutingOptions {
+            transfer_penalty: DEFAULT_TRANSFER_PENALTY,
+            objective: Objective::default(),
+        }
+    }
 }
 
 pub struct Data {
-    pub raw: Vec<StationRaw>,
+    /// by [`Line::id`]
     pub lines: HashMap<String, Line>,
     pub stations: HashMap<String, Station>,
-    pub graph: Graph<Station, GraphDistanceF32>,
+    pub graph: Graph<RouteNode, GraphDistanceF32, Hop>,
+    entrances: HashMap<String, NodeId>,
+    exits: HashMap<String, NodeId>,
+}
+
+impl Data {
+    pub fn entrance(&self, station: &Station) -> NodeId {
+        self.entrances[&station.id()]
+    }
+
+    pub fn exit(&self, station: &Station) -> NodeId {
+        self.exits[&station.id()]
+    }
+
+    /// Platforms of `station`, one per line and direction; a route passes the station if it
+    /// reaches any
+    pub fn platforms(&self, station: &Station) -> Vec<NodeId> {
+        self.graph
+            .adjacent(self.entrance(station))
+            .map(|edge| edge.to)
+            .collect()
+    }
+
+    /// Straight-line km between the ends of `edge`, whatever the objective weighs it at
+    pub fn ride_distance(&self, edge: &GraphEdge<'_, GraphDistanceF32, Hop>) -> f32 {
+        self.graph[edge.from]
+            .station
+            .distance_to(&self.graph[edge.to].station)
+    }
+
+    /// A* heuristic towards `to`: the straight-line distance, which no ride can beat under
+    /// any objective; shrunk slightly so rounding never makes it overestimate
+    pub fn heuristic<'a>(&'a self, to: &'a Station) -> impl Fn(NodeId) -> GraphDistanceF32 + 'a {
+        move |node| GraphDistanceF32::new(self.graph[node].station.distance_to(to) * 0.999)
+    }
+}
+
+/// Closed stations and suspended lines a route has to do without
+#[derive(Default)]
+pub struct Avoid {
+    /// trains still pass them, but nobody boards, alights or transfers there
+    pub stations: Vec<Station>,
+    pub lines: Vec<Line>,
+}
+
+impl Avoid {
+    /// Accepts every node but the entrances and exits of closed stations
+    pub fn nodes<'a>(&'a self, data: &'a Data) -> impl Fn(NodeId) -> bool + 'a {
+        move |node| {
+            let node = &data.graph[node];
+            matches!(node.kind, RouteNodeKind::Platform(..))
+                || !self.stations.contains(&node.station)
+        }
+    }
+
+    /// Accepts every hop but those on suspended lines and transfers at closed stations
+    pub fn hops<'a>(
+        &'a self,
+        data: &'a Data,
+    ) -> impl Fn(&GraphEdge<'_, GraphDistanceF32, Hop>) -> bool + 'a {
+        move |edge| match edge.payload {
+            Hop::Board(line) | Hop::Alight(line) | Hop::Ride { line, .. } => {
+                !self.lines.contains(line)
+            }
+            Hop::Transfer { from, to } => {
+                !self.lines.contains(from)
+                    && !self.lines.contains(to)
+                    && !self.stations.contains(&data.graph[edge.from].station)
+            }
+        }
+    }
 }
 
 fn distance(a_latitude: f32, a_longitude: f32, b_latitude: f32, b_longitude: f32) -> f32 {
@@ -103,11 +464,70 @@ fn distance(a_latitude: f32, a_longitude: f32, b_latitude: f32, b_longitude: f32
     EARTH_RADIUS * c
 }
 
-pub fn parse_data(data: &[u8]) -> Result<Data, Box<dyn Error>> {
-    let raw: Vec<StationRaw> = serde_json::from_slice(data)?;
+/// Link of a station to another one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Next,
+    Previous,
+    Transfer,
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Field::Next => "nextStationId",
+            Field::Previous => "previousStationId",
+            Field::Transfer => "transferStationIds",
+        })
+    }
+}
+
+/// Reason station data could not be loaded
+#[derive(Debug)]
+pub enum DataError {
+    Json(serde_json::Error),
+    /// `field` of a station refers to `id`, which no station has
+    UnknownStation {
+        id: String,
+        field: Field,
+    },
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataError::Json(error) => write!(f, "Invalid data: {}", error),
+            DataError::UnknownStation { id, field } => {
+                write!(
+                    f,
+                    "Invalid data: {} refers to unknown station {}",
+                    field, id
+                )
+            }
+        }
+    }
+}
+
+impl Error for DataError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DataError::Json(error) => Some(error),
+            DataError::UnknownStation { .. } => None,
+        }
+    }
+}
+
+/// Reads the stations of JSON station data without checking them
+pub fn parse_raw(data: &[u8]) -> Result<Vec<StationRaw>, DataError> {
+    serde_json::from_slice(data).map_err(DataError::Json)
+}
 
-    let mut station_map = HashMap::<String, Station>::new();
-    let mut line_map = HashMap::<String, Line>::new();
+/// Links stations and lines of `raw` and builds the routing graph
+pub fn build_data(raw: Vec<StationRaw>, options: &RoutingOptions) -> Result<Data, DataError> {
+    let mut stations = Vec::<StationInternal>::new();
+    let mut lines = Vec::<LineInternal>::new();
+    let mut station_indices = HashMap::<String, usize>::new();
+    let mut line_indices = HashMap::<String, usize>::new();
 
     // input ids, name, latitude, longitude
     for StationRaw {
@@ -119,212 +539,522 @@ pub fn parse_data(data: &[u8]) -> Result<Data, Box<dyn Error>> {
         ..
     } in raw.iter()
     {
-        if !station_map.contains_key(id) {
+        if !station_indices.contains_key(id) {
             let mut ids = vec![id.clone()];
             for transfer_station_id in transfer_station_ids {
                 ids.push(transfer_station_id.clone());
             }
-            let result = Station {
-                internal: Rc::new(RefCell::new(StationInternal {
-                    ids: ids.clone(),
-                    name: name.clone(),
-                    lines: HashMap::new(),
-                    latitude: *latitude,
-                    longitude: *longitude,
-                })),
-            };
-            for id in ids {
-                station_map.insert(id, result.clone());
+            for id in ids.iter() {
+                station_indices.insert(id.clone(), stations.len());
             }
+            stations.push(StationInternal {
+                ids,
+                name: name.clone(),
+                lines: HashMap::new(),
+                latitude: *latitude,
+                longitude: *longitude,
+            });
         }
     }
 
     // input StationInternal::lines, LineInternal::stations
     for StationRaw {
         id,
-        line: line_name,
+        line: line_id,
+        line_name,
         next_station_id,
         previous_station_id,
         ..
     } in raw.iter()
     {
-        let station = station_map.get(id).unwrap().clone();
-        let next_station = next_station_id
-            .clone()
-            .map(|id| {
-                station_map
-                    .get(&id)
-                    .map(|station| Rc::downgrade(&station.internal))
-                    .ok_or("Invalid data: no matching next_station")
-            })
-            .transpose()?;
-        let previous_station = previous_station_id
-            .clone()
-            .map(|id| {
-                station_map
-                    .get(&id)
-                    .map(|station| Rc::downgrade(&station.internal))
-                    .ok_or("Invalid data: no matching previous_station")
-            })
-            .transpose()?;
-        let line = if let Some(line) = line_map.get(line_name) {
-            line.internal.borrow_mut().stations.push(station.clone());
-            line.clone()
-        } else {
-            let result = Line {
-                internal: Rc::new(RefCell::new(LineInternal {
-                    name: line_name.clone(),
-                    stations: vec![station.clone()],
-                })),
-            };
-            line_map.insert(line_name.clone(), result.clone());
-            result
+        let station = station_indices[id];
+        let neighbor = |id: &Option<String>, field| {
+            id.as_ref()
+                .map(|id| {
+                    station_indices
+                        .get(id)
+                        .copied()
+                        .ok_or(DataError::UnknownStation {
+                            id: id.clone(),
+                            field,
+                        })
+                })
+                .transpose()
         };
-        let station_line = StationLine {
-            next_station,
-            previous_station,
-            line: Rc::downgrade(&line.internal),
-        };
-        station
-            .internal
-            .borrow_mut()
-            .lines
-            .insert(line_name.clone(), station_line);
-    }
-
-    // fill adjacent matrix
-    let mut values = station_map
-        .iter()
-        .map(|(_, station)| station.clone())
-        .collect::<Vec<_>>();
-    remove_duplicates(&mut values);
-    let index_map = values
-        .iter()
-        .enumerate()
-        .flat_map(|(idx, station)| {
-            station
-                .internal
-                .borrow()
-                .ids
-                .iter()
-                .map(|id| (id.clone(), idx))
-                .collect::<Vec<_>>()
-        })
-        .collect::<HashMap<String, usize>>();
-    let mut adjacent_matrix: Vec<Vec<Option<GraphDistanceF32>>> =
-        vec![vec![None; values.len()]; values.len()];
-    for StationRaw { id, .. } in raw.iter() {
-        let from_index = *index_map.get(id).unwrap();
-        let from = station_map.get(id).unwrap();
-        for (
-            line_name,
+        let next_station = neighbor(next_station_id, Field::Next)?;
+        let previous_station = neighbor(previous_station_id, Field::Previous)?;
+        let line = *line_indices.entry(line_id.clone()).or_insert_with(|| {
+            lines.push(LineInternal {
+                id: line_id.clone(),
+                name: line_name.clone().unwrap_or_else(|| line_id.clone()),
+                stations: Vec::new(),
+            });
+            lines.len() - 1
+        });
+        lines[line].stations.push(station);
+        stations[station].lines.insert(
+            line_id.clone(),
             StationLine {
                 next_station,
                 previous_station,
-                ..
+                line,
             },
-        ) in from.internal.borrow().lines.iter()
-        {
-            let mut previous = from.internal.clone();
-            let mut next_station = next_station.clone();
-            let mut next_station_distance_sum = 0f32;
-            while let Some(next) = next_station.clone() {
-                let current = next.upgrade().unwrap();
-                let (&current_index, next) = {
-                    let current_borrow = current.borrow();
-                    let previous_borrow = previous.borrow();
-                    next_station_distance_sum += distance(
-                        previous_borrow.latitude,
-                        previous_borrow.longitude,
-                        current_borrow.latitude,
-                        current_borrow.longitude,
-                    );
-                    (
-                        index_map.get(&current.borrow().ids[0]).unwrap(),
-                        current
-                            .borrow()
-                            .lines
-                            .get(line_name)
-                            .unwrap()
-                            .next_station
-                            .clone(),
-                    )
-                };
-                if from_index == current_index {
-                    // loop
-                    break;
-                }
-                adjacent_matrix[from_index][current_index] =
-                    Some(GraphDistanceF32::new(next_station_distance_sum));
+        );
+    }
+
+    // frozen from here on
+    let network = Arc::new(Network { stations, lines });
+    let station_map = station_indices
+        .into_iter()
+        .map(|(id, index)| {
+            let station = Station {
+                network: network.clone(),
+                index,
+            };
+            (id, station)
+        })
+        .collect::<HashMap<_, _>>();
+    let line_map = line_indices
+        .into_iter()
+        .map(|(id, index)| {
+            let line = Line {
+                network: network.clone(),
+                index,
+            };
+            (id, line)
+        })
+        .collect::<HashMap<_, _>>();
 
-                previous = current.clone();
-                next_station = next;
+    // one entrance, one exit and one platform per line and direction for every station
+    let stations = (0..network.stations.len())
+        .map(|index| Station {
+            network: network.clone(),
+            index,
+        })
+        .collect::<Vec<_>>();
+    let mut builder = GraphBuilder::<RouteNode, GraphDistanceF32, Hop>::new();
+    let mut entrances = HashMap::<String, NodeId>::new();
+    let mut exits = HashMap::<String, NodeId>::new();
+    let mut platforms = HashMap::<(String, String, Direction), NodeId>::new();
+    for station in stations.iter() {
+        let entrance = builder.add_node(RouteNode {
+            station: station.clone(),
+            kind: RouteNodeKind::Entrance,
+        });
+        entrances.insert(station.id(), entrance);
+        let exit = builder.add_node(RouteNode {
+            station: station.clone(),
+            kind: RouteNodeKind::Exit,
+        });
+        exits.insert(station.id(), exit);
+        for (line, _, _) in station.lines() {
+            for direction in [Direction::Up, Direction::Down] {
+                let platform = builder.add_node(RouteNode {
+                    station: station.clone(),
+                    kind: RouteNodeKind::Platform(line.clone(), direction),
+                });
+                platforms.insert((station.id(), line.id(), direction), platform);
             }
+        }
+    }
 
-            let mut previous = from.internal.clone();
-            let mut next_station = previous_station.clone();
-            let mut next_station_distance_sum = 0f32;
-            while let Some(next) = next_station.clone() {
-                let current = next.upgrade().unwrap();
-                let (&current_index, next) = {
-                    let current_borrow = current.borrow();
-                    let previous_borrow = previous.borrow();
-                    next_station_distance_sum += distance(
-                        previous_borrow.latitude,
-                        previous_borrow.longitude,
-                        current_borrow.latitude,
-                        current_borrow.longitude,
+    // fill edges
+    let objective = options.objective;
+    let transfer = GraphDistanceF32::new(objective.transfer(options.transfer_penalty));
+    for station in stations.iter() {
+        let entrance = entrances[&station.id()];
+        let exit = exits[&station.id()];
+        let station_platforms = station
+            .lines()
+            .into_iter()
+            .flat_map(|(line, previous, next)| {
+                [(previous, Direction::Up), (next, Direction::Down)].map(|(neighbor, direction)| {
+                    let platform = platforms[&(station.id(), line.id(), direction)];
+                    builder.add_edge(
+                        entrance,
+                        platform,
+                        GraphDistanceF32::new(objective.board()),
+                        Hop::Board(line.clone()),
+                    );
+                    builder.add_edge(
+                        platform,
+                        exit,
+                        GraphDistanceF32::zero(),
+                        Hop::Alight(line.clone()),
+                    );
+                    if let Some(neighbor) = neighbor {
+                        builder.add_edge(
+                            platform,
+                            platforms[&(neighbor.id(), line.id(), direction)],
+                            GraphDistanceF32::new(objective.ride(station.distance_to(&neighbor))),
+                            Hop::Ride {
+                                line: line.clone(),
+                                direction,
+                            },
+                        );
+                    }
+                    (platform, line.clone())
+                })
+            })
+            .collect::<Vec<_>>();
+        // turning back on the same line costs as much as changing lines
+        for (from, from_line) in station_platforms.iter() {
+            for (to, to_line) in station_platforms.iter() {
+                if from != to {
+                    builder.add_edge(
+                        *from,
+                        *to,
+                        transfer.clone(),
+                        Hop::Transfer {
+                            from: from_line.clone(),
+                            to: to_line.clone(),
+                        },
                     );
-                    (
-                        index_map.get(&current.borrow().ids[0]).unwrap(),
-                        current
-                            .borrow()
-                            .lines
-                            .get(line_name)
-                            .unwrap()
-                            .previous_station
-                            .clone(),
-                    )
-                };
-                if from_index == current_index {
-                    // loop
-                    break;
                 }
-                adjacent_matrix[from_index][current_index] =
-                    match adjacent_matrix[from_index][current_index].clone() {
-                        Some(previous) => {
-                            Some(previous.min(GraphDistanceF32::new(next_station_distance_sum)))
-                        }
-                        None => Some(GraphDistanceF32::new(next_station_distance_sum)),
-                    };
-
-                previous = current.clone();
-                next_station = next;
             }
         }
     }
 
     // result
-    let graph = Graph::new(values, adjacent_matrix)?;
+    let graph = builder.build();
     Ok(Data {
-        raw,
         lines: line_map,
         stations: station_map,
         graph,
+        entrances,
+        exits,
     })
 }
 
-fn remove_duplicates<T: Eq>(vec: &mut Vec<T>) {
-    let mut i = 0;
-    while i < vec.len() {
-        let mut j = i + 1;
-        while j < vec.len() {
-            if vec[i] == vec[j] {
-                vec.remove(j);
+#[cfg(test)]
+mod tests {
+    use my_trash_bin_240704_lib::{
+        astar, astar_with_stats, dijkstra_filtered, dijkstra_with_stats, shortest_path_tree,
+        via::{via_path, via_path_unordered},
+    };
+
+    use crate::itinerary::Itinerary;
+
+    use super::*;
+
+    #[test]
+    fn astar_matches_dijkstra() {
+        let data = build_data(
+            parse_raw(include_bytes!("../../data.json")).unwrap(),
+            &RoutingOptions::default(),
+        )
+        .unwrap();
+        let mut stations = data.stations.values().cloned().collect::<Vec<_>>();
+        stations.sort_by_key(Station::id);
+        stations.dedup();
+
+        let (mut dijkstra_expanded, mut astar_expanded) = (0, 0);
+        for from in stations.iter().step_by(37) {
+            for to in stations.iter().step_by(41) {
+                let (from_node, to_node) = (data.entrance(from), data.exit(to));
+                let (expected, dijkstra_stats) =
+                    dijkstra_with_stats(&data.graph, from_node, to_node).unwrap();
+                let (path, astar_stats) =
+                    astar_with_stats(&data.graph, from_node, to_node, data.heuristic(to)).unwrap();
+                let cost = |path: Vec<GraphEdge<GraphDistanceF32, Hop>>| {
+                    path.iter().fold(0f32, |sum, edge| sum + **edge.distance)
+                };
+                let (expected, path) = (expected.map(cost), path.map(cost));
+                assert_eq!(path.is_some(), expected.is_some());
+                if let (Some(expected), Some(path)) = (expected, path) {
+                    assert!(
+                        (expected - path).abs() < 1e-3,
+                        "{} -> {}",
+                        from.name(),
+                        to.name()
+                    );
+                }
+                dijkstra_expanded += dijkstra_stats.expanded;
+                astar_expanded += astar_stats.expanded;
+            }
+        }
+        assert!(astar_expanded < dijkstra_expanded);
+    }
+
+    #[test]
+    fn avoid_stations_and_lines() {
+        let data = build_data(
+            parse_raw(include_bytes!("../../data.json")).unwrap(),
+            &RoutingOptions::default(),
+        )
+        .unwrap();
+        let route = |from: &str, to: &str, avoid: &Avoid| {
+            let (from, to) = (&data.stations[from], &data.stations[to]);
+            dijkstra_filtered(
+                &data.graph,
+                data.entrance(from),
+                data.exit(to),
+                avoid.nodes(&data),
+                avoid.hops(&data),
+            )
+            .unwrap()
+            .map(|path| Itinerary::from_path(&data.graph, &path))
+        };
+        // 잠실 to 광화문, usually changing from 2호선 to 5호선 at 을지로4가
+        let (from, to) = ("2호선_016", "5호선_024");
+        let transfer = data.stations["5호선_026"].clone();
+        let usual = route(from, to, &Avoid::default()).unwrap();
+        assert_eq!(usual.legs[0].stations.last(), Some(&transfer));
+
+        let closed = Avoid {
+            stations: vec![transfer.clone()],
+            lines: Vec::new(),
+        };
+        let detour = route(from, to, &closed).unwrap();
+        assert!(detour != usual);
+        assert!(detour.legs.iter().all(|leg| {
+            leg.stations.first() != Some(&transfer) && leg.stations.last() != Some(&transfer)
+        }));
+        // trains still pass the closed station
+        assert!(route("2호선_003", "2호선_005", &closed).is_some());
+
+        let suspended = Avoid {
+            stations: Vec::new(),
+            lines: vec![data.lines["2호선"].clone()],
+        };
+        let detour = route(from, to, &suspended).unwrap();
+        assert!(detour.legs.iter().all(|leg| leg.line.name() != "2호선"));
+    }
+
+    #[test]
+    fn objectives() {
+        let raw = parse_raw(include_bytes!("../../data.json")).unwrap();
+        let [distance, stops, transfers, time] = [
+            Objective::Distance,
+            Objective::Stops,
+            Objective::Transfers,
+            Objective::Time,
+        ]
+        .map(|objective| {
+            let options = RoutingOptions {
+                objective,
+                ..RoutingOptions::default()
+            };
+            build_data(raw.clone(), &options).unwrap()
+        });
+        let route = |data: &Data, from: &Station, to: &Station| {
+            let (from, to) = (&data.stations[&from.id()], &data.stations[&to.id()]);
+            let path = astar(
+                &data.graph,
+                data.entrance(from),
+                data.exit(to),
+                data.heuristic(to),
+            )
+            .unwrap();
+            path.map(|path| Itinerary::from_path(&data.graph, &path))
+        };
+
+        let mut stations = distance.stations.values().cloned().collect::<Vec<_>>();
+        stations.sort_by_key(Station::id);
+        stations.dedup();
+        for from in stations.iter().step_by(37) {
+            for to in stations.iter().step_by(41) {
+                let Some(shortest) = route(&distance, from, to) else {
+                    continue;
+                };
+                let fewest_stops = route(&stops, from, to).unwrap();
+                let fewest_transfers = route(&transfers, from, to).unwrap();
+                let fastest = route(&time, from, to).unwrap();
+                for other in [&shortest, &fewest_transfers, &fastest] {
+                    assert!(fewest_stops.stops() <= other.stops());
+                }
+                for other in [&shortest, &fewest_stops, &fastest] {
+                    assert!(fewest_transfers.transfers() <= other.transfers());
+                }
+                let penalized = |itinerary: &Itinerary| {
+                    itinerary.distance() + DEFAULT_TRANSFER_PENALTY * itinerary.transfers() as f32
+                };
+                for other in [&fewest_stops, &fewest_transfers, &fastest] {
+                    assert!(penalized(&shortest) <= penalized(other) + 1e-3);
+                }
+            }
+        }
+
+        // 잠실 to 김포공항: 9호선 all the way is shortest, 공항철도 stops less often
+        let (from, to) = (
+            &distance.stations["2호선_016"],
+            &distance.stations["5호선_003"],
+        );
+        let shortest = route(&distance, from, to).unwrap();
+        let fewest_stops = route(&stops, from, to).unwrap();
+        assert!(fewest_stops.stops() < shortest.stops());
+        assert!(fewest_stops.distance() > shortest.distance());
+        assert!(route(&time, from, to).unwrap() != shortest);
+
+        // costs read back in what each objective counts
+        let cost = |data: &Data, objective: Objective| {
+            let (from, to) = (&data.stations[&from.id()], &data.stations[&to.id()]);
+            let tree = shortest_path_tree(&data.graph, data.entrance(from)).unwrap();
+            objective.format_cost(**tree.distance(data.exit(to)).unwrap())
+        };
+        let fewest_transfers = route(&transfers, from, to).unwrap();
+        assert_eq!(
+            cost(&distance, Objective::Distance),
+            format!(
+                "{:.1} km",
+                shortest.distance() + DEFAULT_TRANSFER_PENALTY * shortest.transfers() as f32
+            )
+        );
+        assert!(cost(&stops, Objective::Stops)
+            .starts_with(&format!("{} stops + ", fewest_stops.stops())));
+        assert!(cost(&transfers, Objective::Transfers)
+            .starts_with(&format!("{} transfers + ", fewest_transfers.transfers())));
+        assert!(cost(&time, Objective::Time).ends_with(" min"));
+    }
+
+    #[test]
+    fn via_stations() {
+        let data = build_data(
+            parse_raw(include_bytes!("../../data.json")).unwrap(),
+            &RoutingOptions::default(),
+        )
+        .unwrap();
+        // 잠실 to 광화문 through 서울역 and 강남
+        let (from, to) = (&data.stations["2호선_016"], &data.stations["5호선_024"]);
+        let (seoul, gangnam) = (&data.stations["1호선_037"], &data.stations["2호선_022"]);
+        let route = |via: &[&Station], any_order: bool| {
+            let via = via
+                .iter()
+                .map(|station| data.platforms(station))
+                .collect::<Vec<_>>();
+            let (from, to) = (data.entrance(from), data.exit(to));
+            let path = if any_order {
+                via_path_unordered(&data.graph, from, &via, to).unwrap()
             } else {
-                j += 1;
+                via_path(&data.graph, from, &via, to).unwrap()
+            }
+            .unwrap();
+            Itinerary::from_path(&data.graph, &path)
+        };
+        let legs = |itinerary: &Itinerary| {
+            itinerary
+                .legs
+                .iter()
+                .map(|leg| {
+                    let (first, last) = (&leg.stations[0], leg.stations.last().unwrap());
+                    format!("{} {}-{}", leg.line.name(), first.name(), last.name())
+                })
+                .collect::<Vec<_>>()
+        };
+        // names of the stations passed in order, once at every transfer
+        let names = |itinerary: &Itinerary| {
+            let mut names = Vec::<String>::new();
+            for station in itinerary.legs.iter().flat_map(|leg| leg.stations.iter()) {
+                if names.last() != Some(&station.name()) {
+                    names.push(station.name());
+                }
+            }
+            names
+        };
+        let first = |names: &[String], station: &Station| {
+            let position = names.iter().position(|name| *name == station.name());
+            position.unwrap_or_else(|| panic!("{} not passed", station.name()))
+        };
+        let last = |names: &[String], station: &Station| {
+            let position = names.iter().rposition(|name| *name == station.name());
+            position.unwrap_or_else(|| panic!("{} not passed", station.name()))
+        };
+
+        let direct = route(&[], false);
+        assert_eq!(
+            legs(&direct),
+            vec![
+                "2호선 잠실(송파구청)-을지로4가",
+                "5호선 을지로4가-광화문(세종문화회관)"
+            ]
+        );
+        assert!(!names(&direct).contains(&seoul.name()));
+
+        let through = route(&[seoul], false);
+        assert_eq!(
+            legs(&through),
+            vec![
+                "2호선 잠실(송파구청)-동대문역사문화공원",
+                "4호선 동대문역사문화공원-서울역",
+                "1호선 서울역-종로3가",
+                "5호선 종로3가-광화문(세종문화회관)"
+            ]
+        );
+        assert_eq!(
+            names(&through)[11..],
+            [
+                "동대문역사문화공원",
+                "충무로",
+                "명동",
+                "회현(남대문시장)",
+                "서울역",
+                "시청",
+                "종각",
+                "종로3가",
+                "광화문(세종문화회관)"
+            ]
+        );
+        assert!(through.distance() > direct.distance());
+        // turning back costs a transfer, so a route only rides back the way it came to reach a
+        // via station it cannot pass otherwise
+        let turns_back = |itinerary: &Itinerary| {
+            itinerary
+                .legs
+                .windows(2)
+                .filter(|pair| pair[0].line == pair[1].line)
+                .count()
+        };
+        assert_eq!(turns_back(&through), 0);
+
+        // 서울역 then 강남 turns back at 강남, 강남 then 서울역 runs round the south
+        let ordered = route(&[seoul, gangnam], false);
+        let names_ordered = names(&ordered);
+        assert!(first(&names_ordered, seoul) < last(&names_ordered, gangnam));
+        assert_eq!(
+            legs(&ordered),
+            vec![
+                "2호선 잠실(송파구청)-동대문역사문화공원",
+                "4호선 동대문역사문화공원-동작(현충원)",
+                "9호선 동작(현충원)-신논현",
+                "신분당선 신논현-강남",
+                "신분당선 강남-신사",
+                "3호선 신사-종로3가",
+                "5호선 종로3가-광화문(세종문화회관)"
+            ]
+        );
+        assert_eq!(turns_back(&ordered), 1);
+        let reversed = route(&[gangnam, seoul], false);
+        let names_reversed = names(&reversed);
+        assert!(first(&names_reversed, gangnam) < last(&names_reversed, seoul));
+        assert_eq!(
+            legs(&reversed),
+            vec![
+                "2호선 잠실(송파구청)-사당",
+                "4호선 사당-동대문역사문화공원",
+                "5호선 동대문역사문화공원-광화문(세종문화회관)"
+            ]
+        );
+
+        let best = route(&[seoul, gangnam], true);
+        assert_eq!(legs(&best), legs(&reversed));
+        assert!(best.distance() <= ordered.distance().min(reversed.distance()) + 1e-3);
+        assert!(best.distance() < ordered.distance());
+    }
+
+    #[test]
+    fn unknown_station() {
+        let raw = parse_raw(
+            br#"[{"id": "a_1", "name": "a", "line": "a", "nextStationId": null,
+                "previousStationId": "a_0", "transferStationIds": [],
+                "latitude": 37.5, "longitude": 127.0}]"#,
+        )
+        .unwrap();
+        match build_data(raw, &RoutingOptions::default()) {
+            Err(DataError::UnknownStation { id, field }) => {
+                assert_eq!((id.as_str(), field), ("a_0", Field::Previous))
             }
+            _ => panic!("a_0 should be unknown"),
         }
-        i += 1;
+        assert!(matches!(parse_raw(b"{}"), Err(DataError::Json(_))));
     }
 }
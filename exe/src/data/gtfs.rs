@@ -0,0 +1,449 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt, fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use super::StationRaw;
+
+#[derive(serde::Deserialize)]
+struct StopRow {
+    stop_id: String,
+    stop_name: Option<String>,
+    stop_lat: Option<f32>,
+    stop_lon: Option<f32>,
+    location_type: Option<u8>,
+    parent_station: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct RouteRow {
+    route_id: String,
+    route_short_name: Option<String>,
+    route_long_name: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct TripRow {
+    route_id: String,
+    trip_id: String,
+    direction_id: Option<u8>,
+}
+
+#[derive(serde::Deserialize)]
+struct StopTimeRow {
+    trip_id: String,
+    stop_id: String,
+    stop_sequence: u32,
+}
+
+#[derive(serde::Deserialize)]
+struct TransferRow {
+    from_stop_id: String,
+    to_stop_id: String,
+    transfer_type: Option<u8>,
+}
+
+/// Reason a GTFS feed could not be loaded
+#[derive(Debug)]
+pub enum GtfsError {
+    Io(io::Error),
+    Zip(zip::result::ZipError),
+    /// A required file is not in the feed
+    MissingFile(&'static str),
+    Read {
+        file: &'static str,
+        error: io::Error,
+    },
+    Csv {
+        file: &'static str,
+        error: csv::Error,
+    },
+    UnknownStop(String),
+    UnknownParentStation(String),
+    UnknownRoute(String),
+    /// A station served by a trip has no latitude or longitude
+    MissingCoordinates(String),
+}
+
+impl fmt::Display for GtfsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GtfsError::Io(error) => write!(f, "{}", error),
+            GtfsError::Zip(error) => write!(f, "{}", error),
+            GtfsError::MissingFile(file) => write!(f, "GTFS feed has no {}", file),
+            GtfsError::Read { file, error } => write!(f, "Failed to read {}: {}", file, error),
+            GtfsError::Csv { file, error } => write!(f, "Invalid {}: {}", file, error),
+            GtfsError::UnknownStop(id) => write!(f, "Unknown stop: {}", id),
+            GtfsError::UnknownParentStation(id) => write!(f, "Unknown parent station: {}", id),
+            GtfsError::UnknownRoute(id) => write!(f, "Unknown route: {}", id),
+            GtfsError::MissingCoordinates(id) => write!(f, "Stop {} has no coordinates", id),
+        }
+    }
+}
+
+impl Error for GtfsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GtfsError::Io(error) | GtfsError::Read { error, .. } => Some(error),
+            GtfsError::Zip(error) => Some(error),
+            GtfsError::Csv { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Link between consecutive stations of a trip that the line leaves out, as every station keeps
+/// a single next and previous station on it; the trip branches off the stations linked before
+#[derive(Debug, Clone, PartialEq)]
+pub struct DroppedLink {
+    pub route: String,
+    pub from: String,
+    pub to: String,
+}
+
+impl fmt::Display for DroppedLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Route {} branches from stop {} to stop {}, which is left out",
+            self.route, self.from, self.to
+        )
+    }
+}
+
+/// GTFS feed as a directory or a zip archive of its text files
+enum Feed {
+    Directory(Box<Path>),
+    Zip(zip::ZipArchive<fs::File>),
+}
+
+impl Feed {
+    fn open(path: &Path) -> Result<Feed, GtfsError> {
+        if path.is_dir() {
+            Ok(Feed::Directory(path.into()))
+        } else {
+            let file = fs::File::open(path).map_err(GtfsError::Io)?;
+            Ok(Feed::Zip(
+                zip::ZipArchive::new(file).map_err(GtfsError::Zip)?,
+            ))
+        }
+    }
+
+    /// Contents of `name`, None if the feed lacks it
+    fn file(&mut self, name: &'static str) -> Result<Option<Vec<u8>>, GtfsError> {
+        let result = match self {
+            Feed::Directory(path) => fs::read(path.join(name)),
+            Feed::Zip(archive) => match archive.by_name(name) {
+                Ok(mut file) => {
+                    let mut bytes = Vec::new();
+                    file.read_to_end(&mut bytes).map(|_| bytes)
+                }
+                Err(zip::result::ZipError::FileNotFound) => {
+                    Err(io::Error::from(io::ErrorKind::NotFound))
+                }
+                Err(error) => return Err(GtfsError::Zip(error)),
+            },
+        };
+        match result {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(GtfsError::Read { file: name, error }),
+        }
+    }
+
+    fn rows<T: serde::de::DeserializeOwned>(
+        &mut self,
+        name: &'static str,
+        required: bool,
+    ) -> Result<Vec<T>, GtfsError> {
+        let Some(bytes) = self.file(name)? else {
+            return if required {
+                Err(GtfsError::MissingFile(name))
+            } else {
+                Ok(Vec::new())
+            };
+        };
+        csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(bytes.as_slice())
+            .deserialize()
+            .collect::<Result<Vec<T>, _>>()
+            .map_err(|error| GtfsError::Csv { file: name, error })
+    }
+}
+
+/// Finds the representative of `key`, merging paths on the way
+fn find(groups: &mut HashMap<String, String>, key: &str) -> String {
+    let parent = groups.get(key).cloned().unwrap_or_else(|| key.to_string());
+    if parent == key {
+        return parent;
+    }
+    let root = find(groups, &parent);
+    groups.insert(key.to_string(), root.clone());
+    root
+}
+
+/// Reads stops.txt, routes.txt, trips.txt, stop_times.txt and transfers.txt from a GTFS
+/// directory or zip archive: every route becomes a line named after it and identified by its
+/// `route_id`, every station (or stop without a parent) served by it one of its stations, and
+/// stations joined by a transfer or sharing a parent become transfer stations of each other;
+/// also returns the links of branching trips the lines cannot hold
+pub fn load(path: &Path) -> Result<(Vec<StationRaw>, Vec<DroppedLink>), GtfsError> {
+    let mut feed = Feed::open(path)?;
+    let stops = feed.rows::<StopRow>("stops.txt", true)?;
+    let routes = feed.rows::<RouteRow>("routes.txt", true)?;
+    let trips = feed.rows::<TripRow>("trips.txt", true)?;
+    let stop_times = feed.rows::<StopTimeRow>("stop_times.txt", true)?;
+    let transfers = feed.rows::<TransferRow>("transfers.txt", false)?;
+
+    // platforms and entrances are folded into their parent station
+    let stops = stops
+        .into_iter()
+        .map(|stop| (stop.stop_id.clone(), stop))
+        .collect::<HashMap<_, _>>();
+    let station_of = |stop_id: &str| -> Result<&StopRow, GtfsError> {
+        let stop = stops
+            .get(stop_id)
+            .ok_or_else(|| GtfsError::UnknownStop(stop_id.to_string()))?;
+        match (&stop.parent_station, stop.location_type.unwrap_or(0)) {
+            (Some(parent), 0 | 2 | 3 | 4) if !parent.is_empty() => stops
+                .get(parent)
+                .ok_or_else(|| GtfsError::UnknownParentStation(parent.clone())),
+            _ => Ok(stop),
+        }
+    };
+
+    let line_names = routes
+        .iter()
+        .map(|route| {
+            let name = [&route.route_short_name, &route.route_long_name]
+                .into_iter()
+                .flatten()
+                .find(|name| !name.is_empty())
+                .unwrap_or(&route.route_id);
+            (route.route_id.as_str(), name.clone())
+        })
+        .collect::<HashMap<_, _>>();
+
+    // stations visited by every trip, in order
+    let mut trip_stops = HashMap::<&str, Vec<(u32, &str)>>::new();
+    for stop_time in stop_times.iter() {
+        trip_stops
+            .entry(&stop_time.trip_id)
+            .or_default()
+            .push((stop_time.stop_sequence, &stop_time.stop_id));
+    }
+    let mut sequences = Vec::new();
+    for trip in trips.iter() {
+        if !line_names.contains_key(trip.route_id.as_str()) {
+            return Err(GtfsError::UnknownRoute(trip.route_id.clone()));
+        }
+        let line = trip.route_id.as_str();
+        let Some(mut visits) = trip_stops.remove(trip.trip_id.as_str()) else {
+            continue;
+        };
+        visits.sort();
+        let mut stations = Vec::<&str>::new();
+        for (_, stop_id) in visits {
+            let station = station_of(stop_id)?.stop_id.as_str();
+            if stations.last() != Some(&station) {
+                stations.push(station);
+            }
+        }
+        sequences.push((line, trip.direction_id.unwrap_or(0), stations));
+    }
+    // the longest trips of the first direction define the order of stations on a line,
+    // others only fill in what they left out
+    sequences.sort_by(|(a_line, a_direction, a), (b_line, b_direction, b)| {
+        (a_line, a_direction, b.len()).cmp(&(b_line, b_direction, a.len()))
+    });
+
+    let id = |line: &str, station: &str| format!("{}_{}", line, station);
+    let mut next = HashMap::<String, String>::new();
+    let mut previous = HashMap::<String, String>::new();
+    let mut served = Vec::<(String, &str, String)>::new();
+    let mut seen = HashSet::<String>::new();
+    let mut dropped = Vec::<DroppedLink>::new();
+    for (line, _, stations) in sequences.iter() {
+        for station in stations.iter() {
+            if seen.insert(id(line, station)) {
+                served.push((id(line, station), station, line.to_string()));
+            }
+        }
+        for pair in stations.windows(2) {
+            let (from, to) = (id(line, pair[0]), id(line, pair[1]));
+            // the other direction rides the same link backwards
+            if next.get(&from) == Some(&to) || next.get(&to) == Some(&from) {
+                continue;
+            }
+            if !next.contains_key(&from) && !previous.contains_key(&to) {
+                next.insert(from.clone(), to.clone());
+                previous.insert(to, from);
+            } else if !dropped
+                .iter()
+                .any(|link| link.route == *line && link.from == pair[1] && link.to == pair[0])
+            {
+                dropped.push(DroppedLink {
+                    route: line.to_string(),
+                    from: pair[0].to_string(),
+                    to: pair[1].to_string(),
+                });
+            }
+        }
+    }
+
+    // stations joined by transfers.txt are the same station for routing
+    let mut groups = HashMap::<String, String>::new();
+    for transfer in transfers
+        .iter()
+        .filter(|transfer| transfer.transfer_type != Some(3))
+    {
+        let from = find(&mut groups, &station_of(&transfer.from_stop_id)?.stop_id);
+        let to = find(&mut groups, &station_of(&transfer.to_stop_id)?.stop_id);
+        if from != to {
+            groups.insert(from, to);
+        }
+    }
+    let mut members = HashMap::<String, Vec<String>>::new();
+    for (id, station, _) in served.iter() {
+        members
+            .entry(find(&mut groups, station))
+            .or_default()
+            .push(id.clone());
+    }
+
+    let stations = served
+        .iter()
+        .map(|(id, station, line)| {
+            let stop = &stops[*station];
+            let group = &members[&find(&mut groups, station)];
+            Ok(StationRaw {
+                id: id.clone(),
+                name: stop
+                    .stop_name
+                    .clone()
+                    .unwrap_or_else(|| stop.stop_id.clone()),
+                line: line.clone(),
+                line_name: Some(line_names[line.as_str()].clone()).filter(|name| name != line),
+                next_station_id: next.get(id).cloned(),
+                previous_station_id: previous.get(id).cloned(),
+                transfer_station_ids: group.iter().filter(|other| *other != id).cloned().collect(),
+                latitude: stop
+                    .stop_lat
+                    .ok_or_else(|| GtfsError::MissingCoordinates(stop.stop_id.clone()))?,
+                longitude: stop
+                    .stop_lon
+                    .ok_or_else(|| GtfsError::MissingCoordinates(stop.stop_id.clone()))?,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((stations, dropped))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::data::{build_data, Direction, RoutingOptions};
+
+    use super::*;
+
+    #[test]
+    fn load_directory() {
+        let directory = env::temp_dir().join(format!("gtfs-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        for (name, contents) in [
+            (
+                "stops.txt",
+                "stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station\n\
+                 A,Alpha,37.50,127.00,1,\n\
+                 A1,Alpha,37.50,127.00,0,A\n\
+                 A2,Alpha,37.50,127.00,0,A\n\
+                 B,Bravo,37.51,127.01,0,\n\
+                 C,Charlie,37.52,127.02,0,\n\
+                 D,Delta,37.49,126.99,0,\n\
+                 E,Echo,37.52,127.021,0,\n\
+                 F,Foxtrot,37.515,127.03,0,\n",
+            ),
+            (
+                "routes.txt",
+                "route_id,route_short_name,route_long_name,route_type\n\
+                 r1,Red,,1\n\
+                 r2,,Blue Line,1\n\
+                 r3,Red,,3\n",
+            ),
+            (
+                "trips.txt",
+                "route_id,service_id,trip_id,direction_id\n\
+                 r1,s,t1,0\n\
+                 r1,s,t2,1\n\
+                 r2,s,t3,0\n\
+                 r1,s,t4,0\n\
+                 r3,s,t5,0\n",
+            ),
+            (
+                "stop_times.txt",
+                "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+                 t1,08:00:00,08:00:00,A1,1\n\
+                 t1,08:02:00,08:02:00,B,2\n\
+                 t1,08:04:00,08:04:00,C,3\n\
+                 t2,08:00:00,08:00:00,C,1\n\
+                 t2,08:02:00,08:02:00,B,2\n\
+                 t2,08:04:00,08:04:00,A1,3\n\
+                 t3,08:00:00,08:00:00,D,1\n\
+                 t3,08:03:00,08:03:00,A2,2\n\
+                 t3,08:09:00,08:09:00,E,3\n\
+                 t4,09:00:00,09:00:00,B,1\n\
+                 t4,09:02:00,09:02:00,F,2\n\
+                 t5,08:00:00,08:00:00,D,1\n\
+                 t5,08:10:00,08:10:00,B,2\n",
+            ),
+            (
+                "transfers.txt",
+                "from_stop_id,to_stop_id,transfer_type\nC,E,2\n",
+            ),
+        ] {
+            fs::write(directory.join(name), contents).unwrap();
+        }
+
+        let (raw, dropped) = load(&directory).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        // the branch of t4 from B to F cannot follow B to C as well
+        assert_eq!(
+            dropped,
+            vec![DroppedLink {
+                route: "r1".to_string(),
+                from: "B".to_string(),
+                to: "F".to_string()
+            }]
+        );
+        assert_eq!(raw.len(), 9);
+        let data = build_data(raw, &RoutingOptions::default()).unwrap();
+        // r1 and r3 share a name but stay apart
+        assert_eq!(data.lines.len(), 3);
+        assert_eq!(data.lines["r1"].name(), "Red");
+        assert_eq!(data.lines["r1"].stations().len(), 4);
+        assert_eq!(data.lines["r2"].name(), "Blue Line");
+        assert_eq!(data.lines["r2"].stations().len(), 3);
+        assert_eq!(data.lines["r3"].name(), "Red");
+        assert_eq!(data.lines["r3"].stations().len(), 2);
+        // A is shared by both lines, C and E are joined by a transfer
+        assert_eq!(data.stations["r1_A"], data.stations["r2_A"]);
+        assert_eq!(data.stations["r1_C"], data.stations["r2_E"]);
+        assert_eq!(data.stations["r1_A"].name(), "Alpha");
+        let r1 = &data.lines["r1"];
+        let b = &data.stations["r1_B"];
+        assert_eq!(
+            b.neighbor(r1, Direction::Up),
+            Some(data.stations["r1_A"].clone())
+        );
+        assert_eq!(
+            b.neighbor(r1, Direction::Down),
+            Some(data.stations["r1_C"].clone())
+        );
+        assert_eq!(data.stations["r1_F"].neighbor(r1, Direction::Up), None);
+    }
+}
@@ -0,0 +1,553 @@
+use std::{error::Error, fmt};
+
+use super::{Data, Station};
+
+const HANGUL_SYLLABLE_FIRST: u32 = 0xAC00;
+const HANGUL_SYLLABLE_LAST: u32 = 0xD7A3;
+const MEDIAL_COUNT: u32 = 21;
+const FINAL_COUNT: u32 = 28;
+
+/// Reason a query does not name a single station
+#[derive(Debug)]
+pub enum LookupError {
+    UnknownStation(String),
+    /// `query` matches all of `candidates` equally well
+    AmbiguousStation {
+        query: String,
+        candidates: Vec<Station>,
+    },
+}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LookupError::UnknownStation(query) => write!(f, "Unknown station: {}", query),
+            LookupError::AmbiguousStation { query, candidates } => {
+                const MAX_LISTED: usize = 10;
+                let mut listed = candidates
+                    .iter()
+                    .take(MAX_LISTED)
+                    .map(|station| format!("{} ({})", station.name(), station.id()))
+                    .collect::<Vec<_>>();
+                if candidates.len() > MAX_LISTED {
+                    listed.push(format!("and {} more", candidates.len() - MAX_LISTED));
+                }
+                write!(
+                    f,
+                    "Ambiguous station {}, did you mean: {}",
+                    query,
+                    listed.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl Error for LookupError {}
+
+const CHOSEONG: [char; 19] = [
+    'ㄱ', 'ㄲ', 'ㄴ', 'ㄷ', 'ㄸ', 'ㄹ', 'ㅁ', 'ㅂ', 'ㅃ', 'ㅅ', 'ㅆ', 'ㅇ', 'ㅈ', 'ㅉ', 'ㅊ', 'ㅋ',
+    'ㅌ', 'ㅍ', 'ㅎ',
+];
+
+// Revised Romanization of Korean
+const INITIALS: [&str; 19] = [
+    "g", "kk", "n", "d", "tt", "r", "m", "b", "pp", "s", "ss", "", "j", "jj", "ch", "k", "t", "p",
+    "h",
+];
+const MEDIALS: [&str; 21] = [
+    "a", "ae", "ya", "yae", "eo", "e", "yeo", "ye", "o", "wa", "wae", "oe", "yo", "u", "wo", "we",
+    "wi", "yu", "eu", "ui", "i",
+];
+
+const INITIAL_N: u32 = 2;
+const INITIAL_R: u32 = 5;
+const INITIAL_M: u32 = 6;
+const INITIAL_SILENT: u32 = 11;
+
+/// Medials starting with a y or i sound, before which a final consonant is not carried over
+const IOTIZED_MEDIALS: [u32; 7] = [2, 3, 6, 7, 12, 17, 20];
+
+/// Pronounced class of a final consonant (batchim)
+#[derive(Clone, Copy, PartialEq)]
+enum Final {
+    None,
+    K,
+    N,
+    T,
+    L,
+    M,
+    P,
+    Ng,
+}
+
+impl Final {
+    fn from_index(index: u32) -> Final {
+        match index {
+            0 => Final::None,
+            1 | 2 | 3 | 9 | 24 => Final::K,
+            4..=6 => Final::N,
+            7 | 19 | 20 | 22 | 23 | 25 | 27 => Final::T,
+            8 | 11..=13 | 15 => Final::L,
+            10 | 16 => Final::M,
+            14 | 17 | 18 | 26 => Final::P,
+            21 => Final::Ng,
+            _ => unreachable!(),
+        }
+    }
+
+    fn spelling(self) -> &'static str {
+        match self {
+            Final::None => "",
+            Final::K => "k",
+            Final::N => "n",
+            Final::T => "t",
+            Final::L => "l",
+            Final::M => "m",
+            Final::P => "p",
+            Final::Ng => "ng",
+        }
+    }
+
+    /// Spelling when carried over to a following syllable without initial consonant
+    fn carried_over(self) -> Option<&'static str> {
+        match self {
+            Final::K => Some("g"),
+            Final::T => Some("d"),
+            Final::L => Some("r"),
+            Final::P => Some("b"),
+            _ => None,
+        }
+    }
+
+    fn nasalized(self) -> Final {
+        match self {
+            Final::K => Final::Ng,
+            Final::T => Final::N,
+            Final::P => Final::M,
+            class => class,
+        }
+    }
+}
+
+fn decompose(c: char) -> Option<(u32, u32, u32)> {
+    let code = c as u32;
+    if !(HANGUL_SYLLABLE_FIRST..=HANGUL_SYLLABLE_LAST).contains(&code) {
+        return None;
+    }
+    let index = code - HANGUL_SYLLABLE_FIRST;
+    Some((
+        index / (MEDIAL_COUNT * FINAL_COUNT),
+        index % (MEDIAL_COUNT * FINAL_COUNT) / FINAL_COUNT,
+        index % FINAL_COUNT,
+    ))
+}
+
+/// Hangul initial consonants of `text`, e.g. "강남" -> "ㄱㄴ"; other characters are kept as is
+pub fn choseong(text: &str) -> String {
+    text.chars()
+        .map(|c| match decompose(c) {
+            Some((initial, _, _)) => CHOSEONG[initial as usize],
+            None => c,
+        })
+        .collect()
+}
+
+fn is_choseong(c: char) -> bool {
+    CHOSEONG.contains(&c)
+}
+
+/// Syllable by syllable Revised Romanization, without any sound change
+pub fn transliterate(text: &str) -> String {
+    let mut result = String::new();
+    for c in text.chars() {
+        match decompose(c) {
+            Some((initial, medial, final_index)) => {
+                result.push_str(INITIALS[initial as usize]);
+                result.push_str(MEDIALS[medial as usize]);
+                result.push_str(Final::from_index(final_index).spelling());
+            }
+            None => result.extend(c.to_lowercase()),
+        }
+    }
+    result
+}
+
+/// Revised Romanization as pronounced, applying the common consonant assimilations
+/// (e.g. "왕십리" -> "wangsimni", "독립문" -> "dongnimmun", "신림" -> "sillim")
+pub fn romanize(text: &str) -> String {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut result = String::new();
+    // spelling of the current initial when changed by the previous final
+    let mut initial_override: Option<&str> = None;
+
+    for (index, &c) in chars.iter().enumerate() {
+        let Some((initial, medial, final_index)) = decompose(c) else {
+            result.extend(c.to_lowercase());
+            initial_override = None;
+            continue;
+        };
+        result.push_str(
+            initial_override
+                .take()
+                .unwrap_or(INITIALS[initial as usize]),
+        );
+        result.push_str(MEDIALS[medial as usize]);
+
+        let mut class = Final::from_index(final_index);
+        if let Some((following, following_medial, _)) =
+            chars.get(index + 1).and_then(|&c| decompose(c))
+        {
+            match (class, following) {
+                (Final::N | Final::L, INITIAL_R) | (Final::L, INITIAL_N) => {
+                    class = Final::L;
+                    initial_override = Some("l");
+                }
+                (Final::None, _) => {}
+                (_, INITIAL_R) => {
+                    class = class.nasalized();
+                    initial_override = Some(INITIALS[INITIAL_N as usize]);
+                }
+                (_, INITIAL_N | INITIAL_M) => class = class.nasalized(),
+                (_, INITIAL_SILENT) if !IOTIZED_MEDIALS.contains(&following_medial) => {
+                    if let Some(carried_over) = class.carried_over() {
+                        class = Final::None;
+                        initial_override = Some(carried_over);
+                    }
+                }
+                _ => {}
+            }
+        }
+        result.push_str(class.spelling());
+    }
+    result
+}
+
+/// Lowercase letters and digits only, so "Seoul Station" and "seoul-station" compare equal
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// How a station matched a query, from the most to the least specific
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchKind {
+    Id,
+    Name,
+    Prefix,
+    Substring,
+    Choseong,
+    Romanization,
+}
+
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub station: Station,
+    pub kind: MatchKind,
+}
+
+struct Entry {
+    station: Station,
+    ids: Vec<String>,
+    name: String,
+    /// name without the parenthesized alias, e.g. "군자" for "군자(능동)"
+    short_name: String,
+    normalized_name: String,
+    choseong: String,
+    romanized: String,
+    transliterated: String,
+    /// romanized spellings of `short_name`, matched as a whole
+    romanized_short_names: [String; 2],
+}
+
+pub struct StationIndex {
+    entries: Vec<Entry>,
+}
+
+impl StationIndex {
+    pub fn new(data: &Data) -> StationIndex {
+        let mut stations = data.stations.values().cloned().collect::<Vec<_>>();
+        stations.sort_by_key(Station::id);
+        stations.dedup();
+        let entries = stations
+            .into_iter()
+            .map(|station| {
+                let name = station.name();
+                let short_name = name.split('(').next().unwrap_or(&name).to_string();
+                let normalized_name = normalize(&name);
+                Entry {
+                    ids: station.ids(),
+                    choseong: choseong(&normalized_name),
+                    romanized: normalize(&romanize(&name)),
+                    transliterated: normalize(&transliterate(&name)),
+                    romanized_short_names: [
+                        normalize(&romanize(&short_name)),
+                        normalize(&transliterate(&short_name)),
+                    ],
+                    station,
+                    name,
+                    short_name,
+                    normalized_name,
+                }
+            })
+            .collect();
+        StationIndex { entries }
+    }
+
+    /// How `entry` matches `query`, and whether the match is at the start of the compared text
+    fn match_kind(entry: &Entry, query: &str) -> Option<(MatchKind, bool)> {
+        let normalized = normalize(query);
+        if entry.ids.iter().any(|id| id == query) {
+            Some((MatchKind::Id, true))
+        } else if entry.name == query
+            || entry.short_name == query
+            || entry.romanized_short_names.contains(&normalized)
+        {
+            Some((MatchKind::Name, true))
+        } else if normalized.is_empty() {
+            None
+        } else if entry.normalized_name.starts_with(&normalized) {
+            Some((MatchKind::Prefix, true))
+        } else if entry.normalized_name.contains(&normalized) {
+            Some((MatchKind::Substring, false))
+        } else if normalized.chars().all(is_choseong) && entry.choseong.contains(&normalized) {
+            Some((MatchKind::Choseong, entry.choseong.starts_with(&normalized)))
+        } else if normalized.is_ascii()
+            && (entry.romanized.contains(&normalized) || entry.transliterated.contains(&normalized))
+        {
+            Some((
+                MatchKind::Romanization,
+                entry.romanized.starts_with(&normalized)
+                    || entry.transliterated.starts_with(&normalized),
+            ))
+        } else {
+            None
+        }
+    }
+
+    fn search_ranked(&self, query: &str) -> Vec<(MatchKind, bool, &Entry)> {
+        let mut result = self
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                Self::match_kind(entry, query).map(|(kind, at_start)| (kind, at_start, entry))
+            })
+            .collect::<Vec<_>>();
+        result.sort_by(|(a_kind, a_at_start, a), (b_kind, b_at_start, b)| {
+            a_kind
+                .cmp(b_kind)
+                .then(b_at_start.cmp(a_at_start))
+                .then(a.name.chars().count().cmp(&b.name.chars().count()))
+                .then(a.name.cmp(&b.name))
+        });
+        result
+    }
+
+    /// All stations matching `query`, best matches first
+    pub fn search(&self, query: &str) -> Vec<Candidate> {
+        self.search_ranked(query)
+            .into_iter()
+            .map(|(kind, _, entry)| Candidate {
+                station: entry.station.clone(),
+                kind,
+            })
+            .collect()
+    }
+
+    /// The only station matching `query` best, or an error listing the candidates
+    pub fn resolve(&self, query: &str) -> Result<Station, LookupError> {
+        let candidates = self.search_ranked(query);
+        let Some(&(kind, at_start, _)) = candidates.first() else {
+            return Err(LookupError::UnknownStation(query.to_string()));
+        };
+        let mut best = candidates
+            .iter()
+            .take_while(|candidate| candidate.0 == kind && candidate.1 == at_start)
+            .map(|(_, _, entry)| entry.station.clone())
+            .collect::<Vec<_>>();
+        if best.len() == 1 {
+            return Ok(best.remove(0));
+        }
+        Err(LookupError::AmbiguousStation {
+            query: query.to_string(),
+            candidates: best,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{build_data, parse_raw, RoutingOptions};
+
+    use super::*;
+
+    /// One line t_1 ... t_7, plus a second 신림 on its own line
+    fn data() -> Data {
+        let names = [
+            "강남",
+            "강남구청",
+            "군자(능동)",
+            "신림",
+            "서울역",
+            "구로",
+            "구로디지털단지",
+        ];
+        let mut stations = names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let id = |number: usize| {
+                    (1..=names.len())
+                        .contains(&number)
+                        .then(|| format!("t_{}", number))
+                };
+                serde_json::json!({
+                    "id": id(index + 1),
+                    "name": name,
+                    "line": "t",
+                    "previousStationId": id(index),
+                    "nextStationId": id(index + 2),
+                    "transferStationIds": [],
+                    "latitude": 37.5,
+                    "longitude": 127.0 + index as f32 / 100.0,
+                })
+            })
+            .collect::<Vec<_>>();
+        stations.push(serde_json::json!({
+            "id": "u_1",
+            "name": "신림",
+            "line": "u",
+            "previousStationId": null,
+            "nextStationId": null,
+            "transferStationIds": [],
+            "latitude": 37.4,
+            "longitude": 126.9,
+        }));
+        let raw = parse_raw(&serde_json::to_vec(&stations).unwrap()).unwrap();
+        build_data(raw, &RoutingOptions::default()).unwrap()
+    }
+
+    fn ids(candidates: &[Candidate]) -> Vec<(String, MatchKind)> {
+        candidates
+            .iter()
+            .map(|candidate| (candidate.station.id(), candidate.kind))
+            .collect()
+    }
+
+    #[test]
+    fn ranking() {
+        let data = data();
+        let index = StationIndex::new(&data);
+        let search = |query: &str| ids(&index.search(query));
+        let pair = |id: &str, kind| (id.to_string(), kind);
+
+        assert_eq!(search("t_2"), vec![pair("t_2", MatchKind::Id)]);
+        // an exact name beats the longer name it starts
+        assert_eq!(
+            search("강남"),
+            vec![pair("t_1", MatchKind::Name), pair("t_2", MatchKind::Prefix)]
+        );
+        assert_eq!(search("군자"), vec![pair("t_3", MatchKind::Name)]);
+        // matches at the start first, then shorter names
+        assert_eq!(
+            search("구로"),
+            vec![pair("t_6", MatchKind::Name), pair("t_7", MatchKind::Prefix)]
+        );
+        assert_eq!(
+            search("남"),
+            vec![
+                pair("t_1", MatchKind::Substring),
+                pair("t_2", MatchKind::Substring)
+            ]
+        );
+        assert_eq!(
+            search("ㄱㄴ"),
+            vec![
+                pair("t_1", MatchKind::Choseong),
+                pair("t_2", MatchKind::Choseong)
+            ]
+        );
+        assert_eq!(search("ㄴㄷ"), vec![pair("t_3", MatchKind::Choseong)]);
+        assert_eq!(
+            search("gangnam"),
+            vec![
+                pair("t_1", MatchKind::Name),
+                pair("t_2", MatchKind::Romanization)
+            ]
+        );
+        assert_eq!(search("Seoul"), vec![pair("t_5", MatchKind::Romanization)]);
+        assert!(search("부산").is_empty());
+    }
+
+    #[test]
+    fn resolve_stations() {
+        let data = data();
+        let index = StationIndex::new(&data);
+        let resolve = |query: &str| index.resolve(query).map(|station| station.id());
+
+        assert_eq!(resolve("t_4").unwrap(), "t_4");
+        assert_eq!(resolve("강남").unwrap(), "t_1");
+        assert_eq!(resolve("군자(능동)").unwrap(), "t_3");
+        assert_eq!(resolve("ㄱㄹㄷ").unwrap(), "t_7");
+        assert_eq!(resolve("gurodijiteol").unwrap(), "t_7");
+        assert_eq!(
+            resolve("sillim")
+                .map_err(|error| error.to_string())
+                .unwrap_err(),
+            "Ambiguous station sillim, did you mean: 신림 (t_4), 신림 (u_1)"
+        );
+
+        match index.resolve("부산") {
+            Err(LookupError::UnknownStation(query)) => assert_eq!(query, "부산"),
+            _ => panic!("부산 should be unknown"),
+        }
+        // equally good prefixes
+        match index.resolve("강") {
+            Err(error @ LookupError::AmbiguousStation { .. }) => {
+                assert_eq!(
+                    error.to_string(),
+                    "Ambiguous station 강, did you mean: 강남 (t_1), 강남구청 (t_2)"
+                );
+            }
+            _ => panic!("강 should be ambiguous"),
+        }
+        match index.resolve("신림") {
+            Err(LookupError::AmbiguousStation { candidates, .. }) => {
+                let mut ids = candidates.iter().map(Station::id).collect::<Vec<_>>();
+                ids.sort();
+                assert_eq!(ids, vec!["t_4", "u_1"]);
+            }
+            _ => panic!("신림 should be ambiguous"),
+        }
+    }
+
+    #[test]
+    fn choseong_of_names() {
+        assert_eq!(choseong("강남"), "ㄱㄴ");
+        assert_eq!(choseong("서울역"), "ㅅㅇㅇ");
+        assert_eq!(choseong("군자(능동)"), "ㄱㅈ(ㄴㄷ)");
+    }
+
+    #[test]
+    fn transliterate_names() {
+        assert_eq!(transliterate("신림"), "sinrim");
+        assert_eq!(transliterate("서울역"), "seoulyeok");
+    }
+
+    #[test]
+    fn romanize_names() {
+        assert_eq!(romanize("강남"), "gangnam");
+        assert_eq!(romanize("서울역"), "seoulyeok");
+        assert_eq!(romanize("신촌"), "sinchon");
+        assert_eq!(romanize("잠실"), "jamsil");
+        assert_eq!(romanize("왕십리"), "wangsimni");
+        assert_eq!(romanize("독립문"), "dongnimmun");
+        assert_eq!(romanize("신림"), "sillim");
+        assert_eq!(romanize("을지로"), "euljiro");
+        assert_eq!(romanize("종로3가"), "jongno3ga");
+        assert_eq!(romanize("합정"), "hapjeong");
+        assert_eq!(romanize("설악"), "seorak");
+    }
+}
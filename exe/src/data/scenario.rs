@@ -0,0 +1,426 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+};
+
+use my_trash_bin_240704_lib::{batch::parallel_map, shortest_path_tree, NegativeDistance};
+
+use super::{Data, RouteNodeKind, Station, StationRaw};
+
+/// Disruptions to apply on top of station data, as read from a scenario file
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Scenario {
+    /// ids of stations trains pass without stopping, with every line serving them
+    #[serde(default)]
+    closed_stations: Vec<String>,
+    #[serde(default)]
+    closed_segments: Vec<Segment>,
+    #[serde(default)]
+    shuttles: Vec<Shuttle>,
+}
+
+/// Track between two adjacent stations of a line, closed in both directions
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Segment {
+    line: String,
+    from: String,
+    to: String,
+}
+
+/// Temporary line running between two stations in both directions
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Shuttle {
+    from: String,
+    to: String,
+    /// line name, `Shuttle <n>` for the `n`th shuttle if not given
+    name: Option<String>,
+}
+
+/// Reason a scenario cannot be applied
+#[derive(Debug)]
+pub enum ScenarioError {
+    Json(serde_json::Error),
+    UnknownStation(String),
+    /// `from` and `to` are not next to each other on `line`
+    UnknownSegment {
+        line: String,
+        from: String,
+        to: String,
+    },
+    /// a shuttle ends at a station closed by the same scenario
+    ClosedStation(String),
+    DuplicateLine(String),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Json(error) => write!(f, "Invalid scenario: {}", error),
+            ScenarioError::UnknownStation(id) => write!(f, "Unknown station in scenario: {}", id),
+            ScenarioError::UnknownSegment { line, from, to } => write!(
+                f,
+                "{} and {} are not next to each other on {}",
+                from, to, line
+            ),
+            ScenarioError::ClosedStation(id) => {
+                write!(f, "Shuttle to station {}, which is closed", id)
+            }
+            ScenarioError::DuplicateLine(line) => write!(f, "Line {} already exists", line),
+        }
+    }
+}
+
+impl Error for ScenarioError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ScenarioError::Json(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl Scenario {
+    pub fn parse(data: &[u8]) -> Result<Scenario, ScenarioError> {
+        serde_json::from_slice(data).map_err(ScenarioError::Json)
+    }
+
+    /// Station data with closed segments cut, closed stations left out of their lines and
+    /// shuttles added as lines of their own
+    pub fn apply(&self, raw: &[StationRaw]) -> Result<Vec<StationRaw>, ScenarioError> {
+        let mut stations = raw
+            .iter()
+            .map(|station| (station.id.clone(), station.clone()))
+            .collect::<HashMap<_, _>>();
+        let ids = raw
+            .iter()
+            .map(|station| station.id.clone())
+            .collect::<HashSet<_>>();
+        let known = |id: &String| {
+            if ids.contains(id) {
+                Ok(())
+            } else {
+                Err(ScenarioError::UnknownStation(id.clone()))
+            }
+        };
+
+        for Segment { line, from, to } in self.closed_segments.iter() {
+            known(from)?;
+            known(to)?;
+            let mut cut = false;
+            for (a, b) in [(from, to), (to, from)] {
+                if stations[a].line == *line && stations[a].next_station_id.as_ref() == Some(b) {
+                    stations.get_mut(a).unwrap().next_station_id = None;
+                    cut = true;
+                }
+                if stations[b].line == *line && stations[b].previous_station_id.as_ref() == Some(a)
+                {
+                    stations.get_mut(b).unwrap().previous_station_id = None;
+                    cut = true;
+                }
+            }
+            if !cut {
+                return Err(ScenarioError::UnknownSegment {
+                    line: line.clone(),
+                    from: from.clone(),
+                    to: to.clone(),
+                });
+            }
+        }
+
+        // every id of a closed station, whatever line it is on
+        let mut closed = HashSet::new();
+        for id in self.closed_stations.iter() {
+            known(id)?;
+            closed.insert(id.clone());
+            closed.extend(stations[id].transfer_station_ids.iter().cloned());
+        }
+        // trains run from the station before a closed one straight to the one after
+        let skip = |mut id: Option<String>, next: bool| {
+            for _ in 0..stations.len() {
+                match id {
+                    Some(closed_id) if closed.contains(&closed_id) => {
+                        let station = &stations[&closed_id];
+                        id = if next {
+                            station.next_station_id.clone()
+                        } else {
+                            station.previous_station_id.clone()
+                        };
+                    }
+                    _ => return id,
+                }
+            }
+            None
+        };
+        let mut result = raw
+            .iter()
+            .filter(|station| !closed.contains(&station.id))
+            .map(|station| {
+                let station = &stations[&station.id];
+                StationRaw {
+                    next_station_id: skip(station.next_station_id.clone(), true),
+                    previous_station_id: skip(station.previous_station_id.clone(), false),
+                    transfer_station_ids: station
+                        .transfer_station_ids
+                        .iter()
+                        .filter(|id| !closed.contains(*id))
+                        .cloned()
+                        .collect(),
+                    ..station.clone()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut lines = result
+            .iter()
+            .map(|station| station.line.clone())
+            .collect::<HashSet<_>>();
+        for (index, Shuttle { from, to, name }) in self.shuttles.iter().enumerate() {
+            for id in [from, to] {
+                known(id)?;
+                if closed.contains(id) {
+                    return Err(ScenarioError::ClosedStation(id.clone()));
+                }
+            }
+            let line = name
+                .clone()
+                .unwrap_or_else(|| format!("Shuttle {}", index + 1));
+            if !lines.insert(line.clone()) {
+                return Err(ScenarioError::DuplicateLine(line));
+            }
+            let id = |station: &str| format!("{}_{}", line, station);
+            for (station, previous, next) in [(from, None, Some(to)), (to, Some(from), None)] {
+                // joins the other lines of the station as a transfer
+                let group = result
+                    .iter()
+                    .find(|other| other.id == *station)
+                    .map(|other| {
+                        let mut group = other.transfer_station_ids.clone();
+                        group.push(other.id.clone());
+                        group
+                    })
+                    .unwrap_or_default();
+                for other in result.iter_mut() {
+                    if group.contains(&other.id) {
+                        other.transfer_station_ids.push(id(station));
+                    }
+                }
+                let stop = &stations[station];
+                result.push(StationRaw {
+                    id: id(station),
+                    name: stop.name.clone(),
+                    line: line.clone(),
+                    line_name: None,
+                    next_station_id: next.map(|next| id(next)),
+                    previous_station_id: previous.map(|previous| id(previous)),
+                    transfer_station_ids: group,
+                    latitude: stop.latitude,
+                    longitude: stop.longitude,
+                });
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Route whose cost differs between two versions of the data
+pub struct RouteChange {
+    pub from: Station,
+    pub to: Station,
+    /// cost under the objective both versions were built with, None if there is no route;
+    /// see [`Objective::format_cost`](super::Objective::format_cost)
+    pub before: Option<f32>,
+    pub after: Option<f32>,
+}
+
+/// Routes between stations open in both `before` and `after` whose cost changed, routes that
+/// became impossible first, then by decreasing change in cost
+pub fn route_changes(
+    before: &Data,
+    after: &Data,
+    threads: usize,
+) -> Result<Vec<RouteChange>, NegativeDistance> {
+    let mut stations = before.stations.values().cloned().collect::<Vec<_>>();
+    stations.sort_by_key(Station::id);
+    stations.dedup();
+    // the same station in both versions, if still open
+    let stations = stations
+        .into_iter()
+        .filter_map(|station| {
+            let other = after.stations.get(&station.id())?.clone();
+            Some((station, other))
+        })
+        .collect::<Vec<_>>();
+
+    let rows = parallel_map(&stations, threads, |(from, after_from)| {
+        let costs = |data: &Data, from: &Station, to: &dyn Fn(usize) -> Station| {
+            let tree = shortest_path_tree(&data.graph, data.entrance(from))?;
+            Ok::<_, NegativeDistance>(
+                (0..stations.len())
+                    .map(|index| {
+                        let exit = data.exit(&to(index));
+                        debug_assert_eq!(data.graph[exit].kind, RouteNodeKind::Exit);
+                        tree.distance(exit).map(|distance| **distance)
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let before_costs = costs(before, from, &|index| stations[index].0.clone())?;
+        let after_costs = costs(after, after_from, &|index| stations[index].1.clone())?;
+        Ok((0..stations.len())
+            .filter(|&index| stations[index].0 != *from)
+            .filter(|&index| match (before_costs[index], after_costs[index]) {
+                (Some(before), Some(after)) => (before - after).abs() > 1e-3,
+                (before, after) => before.is_some() != after.is_some(),
+            })
+            .map(|index| RouteChange {
+                from: from.clone(),
+                to: stations[index].0.clone(),
+                before: before_costs[index],
+                after: after_costs[index],
+            })
+            .collect::<Vec<_>>())
+    });
+
+    let mut result = rows
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    let change = |change: &RouteChange| match (change.before, change.after) {
+        (Some(before), Some(after)) => after - before,
+        (Some(_), None) => f32::INFINITY,
+        (None, _) => f32::NEG_INFINITY,
+    };
+    result.sort_by(|a, b| change(b).total_cmp(&change(a)));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{build_data, parse_raw, RoutingOptions};
+
+    use super::*;
+
+    #[test]
+    fn apply_to_data() {
+        let raw = parse_raw(include_bytes!("../../../data.json")).unwrap();
+        let options = RoutingOptions::default();
+        // 역삼 closed, 2호선 cut between 교대 and 서초, a shuttle from 강남 to 남부터미널
+        let scenario = Scenario::parse(
+            r#"{
+                "closedStations": ["2호선_021"],
+                "closedSegments": [{"line": "2호선", "from": "2호선_023", "to": "2호선_024"}],
+                "shuttles": [{"from": "2호선_022", "to": "3호선_033"}]
+            }"#
+            .as_bytes(),
+        )
+        .unwrap();
+        let applied = scenario.apply(&raw).unwrap();
+        let station = |id: &str| applied.iter().find(|station| station.id == id);
+        assert!(station("2호선_021").is_none());
+        assert_eq!(
+            station("2호선_020").unwrap().next_station_id.as_deref(),
+            Some("2호선_022")
+        );
+        assert_eq!(station("2호선_023").unwrap().next_station_id, None);
+        assert_eq!(station("2호선_024").unwrap().previous_station_id, None);
+        assert!(station("Shuttle 1_2호선_022")
+            .unwrap()
+            .transfer_station_ids
+            .contains(&"신분당선_004".to_string()));
+        assert!(station("신분당선_004")
+            .unwrap()
+            .transfer_station_ids
+            .contains(&"Shuttle 1_2호선_022".to_string()));
+
+        let data = build_data(applied, &options).unwrap();
+        assert!(data.lines.contains_key("Shuttle 1"));
+        assert!(!data.stations.contains_key("2호선_021"));
+
+        for (scenario, error) in [
+            (
+                r#"{"closedStations": ["x"]}"#,
+                "Unknown station in scenario: x",
+            ),
+            (
+                r#"{"closedSegments": [{"line": "2호선", "from": "2호선_020", "to": "2호선_022"}]}"#,
+                "2호선_020 and 2호선_022 are not next to each other on 2호선",
+            ),
+            (
+                r#"{"closedStations": ["2호선_021"], "shuttles": [{"from": "2호선_021", "to": "2호선_022"}]}"#,
+                "Shuttle to station 2호선_021, which is closed",
+            ),
+            (
+                r#"{"shuttles": [{"from": "2호선_021", "to": "2호선_022", "name": "2호선"}]}"#,
+                "Line 2호선 already exists",
+            ),
+        ] {
+            let result = Scenario::parse(scenario.as_bytes())
+                .unwrap()
+                .apply(&raw)
+                .map(|_| ());
+            assert_eq!(result.unwrap_err().to_string(), error);
+        }
+    }
+
+    #[test]
+    fn changed_routes() {
+        // a1 - a2 - a3 on line a, b1 - b2 on line b with b1 a transfer to a2
+        let raw = parse_raw(
+            r#"[
+                {"id": "a1", "name": "a1", "line": "a", "nextStationId": "a2",
+                    "previousStationId": null, "transferStationIds": [],
+                    "latitude": 37.50, "longitude": 127.00},
+                {"id": "a2", "name": "a2", "line": "a", "nextStationId": "a3",
+                    "previousStationId": "a1", "transferStationIds": ["b1"],
+                    "latitude": 37.50, "longitude": 127.01},
+                {"id": "a3", "name": "a3", "line": "a", "nextStationId": null,
+                    "previousStationId": "a2", "transferStationIds": [],
+                    "latitude": 37.50, "longitude": 127.02},
+                {"id": "b1", "name": "a2", "line": "b", "nextStationId": "b2",
+                    "previousStationId": null, "transferStationIds": ["a2"],
+                    "latitude": 37.50, "longitude": 127.01},
+                {"id": "b2", "name": "b2", "line": "b", "nextStationId": null,
+                    "previousStationId": "b1", "transferStationIds": [],
+                    "latitude": 37.51, "longitude": 127.02}
+            ]"#
+            .as_bytes(),
+        )
+        .unwrap();
+        let scenario = Scenario::parse(
+            br#"{
+                "closedSegments": [{"line": "a", "from": "a3", "to": "a2"}],
+                "shuttles": [{"from": "b2", "to": "a3"}]
+            }"#,
+        )
+        .unwrap();
+        let options = RoutingOptions::default();
+        let before = build_data(raw.clone(), &options).unwrap();
+        let after = build_data(scenario.apply(&raw).unwrap(), &options).unwrap();
+
+        let changes = route_changes(&before, &after, 2)
+            .unwrap()
+            .iter()
+            .map(|change| {
+                (
+                    change.from.id(),
+                    change.to.id(),
+                    change.after.unwrap() > change.before.unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        // longer without the segment, shorter with the shuttle, longest change first
+        assert_eq!(changes.len(), 6);
+        assert!(changes[0].2);
+        for (from, to) in [("a1", "a3"), ("a3", "a1"), ("a2", "a3"), ("a3", "a2")] {
+            assert!(changes.contains(&(from.to_string(), to.to_string(), true)));
+        }
+        assert!(changes.contains(&("b2".to_string(), "a3".to_string(), false)));
+        assert!(changes.contains(&("a3".to_string(), "b2".to_string(), false)));
+    }
+}
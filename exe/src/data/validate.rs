@@ -0,0 +1,464 @@
+use std::{collections::HashMap, fmt};
+
+use super::{distance, Field, StationRaw};
+
+/// Stations further than this from the median position are considered misplaced
+const REGION_RADIUS_KM: f32 = 100.0;
+/// Transfer stations further apart than this are suspicious
+const TRANSFER_DISTANCE_KM: f32 = 1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Problem {
+    /// the id is used by `count` entries
+    DuplicateId {
+        count: usize,
+    },
+    UnknownStation {
+        field: Field,
+        target: String,
+    },
+    /// next or previous station is on another line
+    OtherLine {
+        field: Field,
+        target: String,
+    },
+    /// the target links back to another station with the opposite field
+    AsymmetricLink {
+        field: Field,
+        target: String,
+    },
+    /// the target has no link back, as on a line running in one direction only
+    OneWayLink {
+        field: Field,
+        target: String,
+    },
+    /// the target does not list the station as a transfer station
+    OneWayTransfer {
+        target: String,
+    },
+    DistantTransfer {
+        target: String,
+        km: f32,
+    },
+    /// not connected to most of the network by any line or transfer
+    Unreachable,
+    InvalidCoordinates,
+    OutOfRegion {
+        km: f32,
+    },
+}
+
+impl Problem {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Problem::DuplicateId { .. }
+            | Problem::UnknownStation { .. }
+            | Problem::OtherLine { .. }
+            | Problem::AsymmetricLink { .. }
+            | Problem::InvalidCoordinates
+            | Problem::OutOfRegion { .. } => Severity::Error,
+            Problem::OneWayLink { .. }
+            | Problem::OneWayTransfer { .. }
+            | Problem::DistantTransfer { .. }
+            | Problem::Unreachable => Severity::Warning,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Issue {
+    pub station: String,
+    pub problem: Problem,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.problem.severity() {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}\t{}\t", severity, self.station)?;
+        match &self.problem {
+            Problem::DuplicateId { count } => write!(f, "id is used by {} stations", count),
+            Problem::UnknownStation { field, target } => {
+                write!(f, "{} refers to unknown station {}", field, target)
+            }
+            Problem::OtherLine { field, target } => {
+                write!(f, "{} refers to {} on another line", field, target)
+            }
+            Problem::AsymmetricLink { field, target } => {
+                write!(
+                    f,
+                    "{} is {}, which links back to another station",
+                    field, target
+                )
+            }
+            Problem::OneWayLink { field, target } => {
+                write!(f, "{} is {}, which does not link back", field, target)
+            }
+            Problem::OneWayTransfer { target } => {
+                write!(f, "transfer station {} does not list it back", target)
+            }
+            Problem::DistantTransfer { target, km } => {
+                write!(f, "transfer station {} is {:.1} km away", target, km)
+            }
+            Problem::Unreachable => write!(f, "not connected to the rest of the network"),
+            Problem::InvalidCoordinates => write!(f, "coordinates are not a valid position"),
+            Problem::OutOfRegion { km } => {
+                write!(f, "{:.0} km away from the middle of the network", km)
+            }
+        }
+    }
+}
+
+/// Every problem found in station data, ordered by station
+pub struct Report {
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    pub fn errors(&self) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| issue.problem.severity() == Severity::Error)
+            .count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.issues.len() - self.errors()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in self.issues.iter() {
+            writeln!(f, "{}", issue)?;
+        }
+        write!(
+            f,
+            "{} {}, {} {}",
+            self.errors(),
+            if self.errors() == 1 {
+                "error"
+            } else {
+                "errors"
+            },
+            self.warnings(),
+            if self.warnings() == 1 {
+                "warning"
+            } else {
+                "warnings"
+            }
+        )
+    }
+}
+
+fn median(mut values: Vec<f32>) -> f32 {
+    values.sort_by(f32::total_cmp);
+    values.get(values.len() / 2).copied().unwrap_or(0.0)
+}
+
+/// Checks ids, links between stations, transfers, connectivity and coordinates of `raw`
+pub fn validate(raw: &[StationRaw]) -> Report {
+    let mut issues = Vec::new();
+    let mut issue = |station: &str, problem| {
+        issues.push(Issue {
+            station: station.to_string(),
+            problem,
+        })
+    };
+
+    let mut by_id = HashMap::<&str, Vec<&StationRaw>>::new();
+    for station in raw.iter() {
+        by_id.entry(&station.id).or_default().push(station);
+    }
+    for (id, stations) in by_id.iter() {
+        if stations.len() > 1 {
+            issue(
+                id,
+                Problem::DuplicateId {
+                    count: stations.len(),
+                },
+            );
+        }
+    }
+    let by_id = by_id
+        .into_iter()
+        .map(|(id, stations)| (id, stations[0]))
+        .collect::<HashMap<_, _>>();
+
+    for station in raw.iter() {
+        for (field, target, back) in [
+            (Field::Next, &station.next_station_id, Field::Previous),
+            (Field::Previous, &station.previous_station_id, Field::Next),
+        ] {
+            let Some(target) = target else {
+                continue;
+            };
+            let Some(other) = by_id.get(target.as_str()) else {
+                issue(
+                    &station.id,
+                    Problem::UnknownStation {
+                        field,
+                        target: target.clone(),
+                    },
+                );
+                continue;
+            };
+            if other.line != station.line {
+                issue(
+                    &station.id,
+                    Problem::OtherLine {
+                        field,
+                        target: target.clone(),
+                    },
+                );
+                continue;
+            }
+            let link_back = match back {
+                Field::Previous => &other.previous_station_id,
+                _ => &other.next_station_id,
+            };
+            let target = target.clone();
+            match link_back {
+                Some(link_back) if *link_back == station.id => {}
+                Some(_) => issue(&station.id, Problem::AsymmetricLink { field, target }),
+                None => issue(&station.id, Problem::OneWayLink { field, target }),
+            }
+        }
+
+        for target in station.transfer_station_ids.iter() {
+            let Some(other) = by_id.get(target.as_str()) else {
+                issue(
+                    &station.id,
+                    Problem::UnknownStation {
+                        field: Field::Transfer,
+                        target: target.clone(),
+                    },
+                );
+                continue;
+            };
+            let mutual = other.transfer_station_ids.contains(&station.id);
+            if !mutual {
+                issue(
+                    &station.id,
+                    Problem::OneWayTransfer {
+                        target: target.clone(),
+                    },
+                );
+            }
+            let km = distance(
+                station.latitude,
+                station.longitude,
+                other.latitude,
+                other.longitude,
+            );
+            // reported once per mutual pair
+            if km > TRANSFER_DISTANCE_KM && (!mutual || station.id < other.id) {
+                issue(
+                    &station.id,
+                    Problem::DistantTransfer {
+                        target: target.clone(),
+                        km,
+                    },
+                );
+            }
+        }
+    }
+
+    // stations outside the largest group linked by lines and transfers, either way
+    let mut neighbors = HashMap::<&str, Vec<&str>>::new();
+    for station in raw.iter() {
+        let links = station
+            .next_station_id
+            .iter()
+            .chain(station.previous_station_id.iter())
+            .chain(station.transfer_station_ids.iter())
+            .filter(|link| by_id.contains_key(link.as_str()));
+        for link in links {
+            neighbors.entry(&station.id).or_default().push(link);
+            neighbors.entry(link).or_default().push(&station.id);
+        }
+    }
+    let mut components = HashMap::<&str, usize>::new();
+    let mut sizes = Vec::new();
+    for station in raw.iter() {
+        if components.contains_key(station.id.as_str()) {
+            continue;
+        }
+        let component = sizes.len();
+        let mut size = 0;
+        let mut stack = vec![station.id.as_str()];
+        components.insert(&station.id, component);
+        while let Some(id) = stack.pop() {
+            size += 1;
+            for &link in neighbors.get(id).into_iter().flatten() {
+                if !components.contains_key(link) {
+                    components.insert(link, component);
+                    stack.push(link);
+                }
+            }
+        }
+        sizes.push(size);
+    }
+    let largest = (0..sizes.len()).max_by_key(|&component| sizes[component]);
+    for (id, component) in components {
+        if Some(component) != largest {
+            issue(id, Problem::Unreachable);
+        }
+    }
+
+    let valid = raw
+        .iter()
+        .filter(|station| {
+            station.latitude.abs() <= 90.0
+                && station.longitude.abs() <= 180.0
+                && !(station.latitude == 0.0 && station.longitude == 0.0)
+        })
+        .collect::<Vec<_>>();
+    let middle_latitude = median(valid.iter().map(|station| station.latitude).collect());
+    let middle_longitude = median(valid.iter().map(|station| station.longitude).collect());
+    for station in raw.iter() {
+        if !valid.iter().any(|valid| std::ptr::eq(*valid, station)) {
+            issue(&station.id, Problem::InvalidCoordinates);
+            continue;
+        }
+        let km = distance(
+            station.latitude,
+            station.longitude,
+            middle_latitude,
+            middle_longitude,
+        );
+        if km > REGION_RADIUS_KM {
+            issue(&station.id, Problem::OutOfRegion { km });
+        }
+    }
+
+    issues.sort_by(|a, b| {
+        (a.problem.severity(), &a.station).cmp(&(b.problem.severity(), &b.station))
+    });
+    Report { issues }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn station(
+        id: &str,
+        line: &str,
+        previous: Option<&str>,
+        next: Option<&str>,
+        transfers: &[&str],
+        position: (f32, f32),
+    ) -> StationRaw {
+        StationRaw {
+            id: id.to_string(),
+            name: id.to_string(),
+            line: line.to_string(),
+            line_name: None,
+            next_station_id: next.map(str::to_string),
+            previous_station_id: previous.map(str::to_string),
+            transfer_station_ids: transfers.iter().map(|id| id.to_string()).collect(),
+            latitude: position.0,
+            longitude: position.1,
+        }
+    }
+
+    #[test]
+    fn finds_every_problem() {
+        let raw = vec![
+            station("a1", "a", None, Some("a2"), &["b1"], (37.50, 127.00)),
+            station("a2", "a", Some("a1"), Some("a3"), &[], (37.51, 127.00)),
+            // links back to a1 instead of a2, and its next station is on another line
+            station("a3", "a", Some("a1"), Some("b2"), &[], (37.52, 127.00)),
+            station("b1", "b", None, Some("b2"), &["d2"], (37.50, 127.001)),
+            station("b2", "b", Some("b1"), Some("zz"), &["a2"], (37.60, 127.00)),
+            // runs from d1 to d2 only
+            station("d1", "d", None, Some("d2"), &[], (37.50, 127.01)),
+            station("d2", "d", None, None, &["b1"], (37.50, 127.011)),
+            station("c1", "c", None, None, &[], (0.0, 0.0)),
+            station("c1", "c", None, None, &[], (35.10, 129.00)),
+        ];
+        let report = validate(&raw);
+        let problems = report
+            .issues
+            .iter()
+            .map(|issue| (issue.station.as_str(), issue.problem.clone()))
+            .collect::<Vec<_>>();
+
+        let target = |id: &str| id.to_string();
+        for expected in [
+            (
+                "a2",
+                Problem::AsymmetricLink {
+                    field: Field::Next,
+                    target: target("a3"),
+                },
+            ),
+            (
+                "a3",
+                Problem::OtherLine {
+                    field: Field::Next,
+                    target: target("b2"),
+                },
+            ),
+            (
+                "b2",
+                Problem::UnknownStation {
+                    field: Field::Next,
+                    target: target("zz"),
+                },
+            ),
+            ("c1", Problem::DuplicateId { count: 2 }),
+            ("c1", Problem::InvalidCoordinates),
+            (
+                "a1",
+                Problem::OneWayTransfer {
+                    target: target("b1"),
+                },
+            ),
+            (
+                "b2",
+                Problem::OneWayTransfer {
+                    target: target("a2"),
+                },
+            ),
+            (
+                "b2",
+                Problem::DistantTransfer {
+                    target: target("a2"),
+                    km: problems
+                        .iter()
+                        .find_map(|(_, problem)| match problem {
+                            Problem::DistantTransfer { km, .. } => Some(*km),
+                            _ => None,
+                        })
+                        .unwrap(),
+                },
+            ),
+            ("c1", Problem::Unreachable),
+        ] {
+            assert!(problems.contains(&expected), "{:?} missing", expected);
+        }
+        assert!(problems
+            .iter()
+            .any(|(id, problem)| *id == "c1" && matches!(problem, Problem::OutOfRegion { .. })));
+        assert_eq!(report.errors(), 7);
+        assert_eq!(report.warnings(), 5);
+        assert_eq!(
+            report
+                .issues
+                .iter()
+                .map(|issue| issue.problem.severity())
+                .max(),
+            Some(Severity::Warning)
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use serde_json::{json, Value};
+
+use crate::{
+    data::{Data, Direction, Station},
+    itinerary::Itinerary,
+};
+
+/// Colour routes are drawn in, over the network
+const ROUTE_STROKE: &str = "#e6194b";
+
+fn position(station: &Station) -> Value {
+    let (latitude, longitude) = station.coordinates();
+    json!([longitude, latitude])
+}
+
+/// A Point for every station and a MultiLineString for every line
+pub fn network(data: &Data) -> Vec<Value> {
+    let mut stations = data.stations.values().cloned().collect::<Vec<_>>();
+    stations.sort_by_key(Station::id);
+    stations.dedup();
+    let mut lines = data.lines.values().collect::<Vec<_>>();
+    lines.sort_by_key(|line| line.name());
+
+    let stations = stations.iter().map(|station| {
+        json!({
+            "type": "Feature",
+            "geometry": { "type": "Point", "coordinates": position(station) },
+            "properties": {
+                "kind": "station",
+                "id": station.id(),
+                "name": station.name(),
+                "lines": station
+                    .lines()
+                    .iter()
+                    .map(|(line, _, _)| line.name())
+                    .collect::<Vec<_>>(),
+            },
+        })
+    });
+    let lines = lines.iter().map(|line| {
+        json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "MultiLineString",
+                "coordinates": line
+                    .runs(Direction::Down)
+                    .iter()
+                    .map(|run| run.iter().map(position).collect::<Vec<_>>())
+                    .collect::<Vec<_>>(),
+            },
+            "properties": { "kind": "line", "name": line.name() },
+        })
+    });
+    stations.chain(lines).collect()
+}
+
+/// A LineString for every leg of `itinerary`, styled to stand out over [`network`]
+pub fn route(itinerary: &Itinerary) -> Vec<Value> {
+    itinerary
+        .legs
+        .iter()
+        .map(|leg| {
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": leg.stations.iter().map(position).collect::<Vec<_>>(),
+                },
+                "properties": {
+                    "kind": "route",
+                    "line": leg.line.name(),
+                    "from": leg.stations.first().map(Station::name),
+                    "to": leg.stations.last().map(Station::name),
+                    "distance_km": leg.distance,
+                    "stroke": ROUTE_STROKE,
+                    "stroke-width": 6,
+                },
+            })
+        })
+        .collect()
+}
+
+pub fn feature_collection(features: Vec<Value>) -> Value {
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+#[cfg(test)]
+mod tests {
+    use my_trash_bin_240704_lib::dijkstra;
+
+    use crate::data::{build_data, parse_raw, RoutingOptions};
+
+    use super::*;
+
+    #[test]
+    fn network_and_route_features() {
+        let data = build_data(
+            parse_raw(include_bytes!("../../data.json")).unwrap(),
+            &RoutingOptions::default(),
+        )
+        .unwrap();
+        let features = network(&data);
+        let kind = |feature: &Value| feature["properties"]["kind"].as_str().unwrap().to_string();
+        let count = |features: &[Value], wanted: &str| {
+            features
+                .iter()
+                .filter(|feature| kind(feature) == wanted)
+                .count()
+        };
+        let mut stations = data.stations.values().map(Station::id).collect::<Vec<_>>();
+        stations.sort();
+        stations.dedup();
+        assert_eq!(count(&features, "station"), stations.len());
+        assert_eq!(count(&features, "line"), data.lines.len());
+        let seoul = features
+            .iter()
+            .find(|feature| feature["properties"]["id"] == "1호선_037")
+            .unwrap();
+        assert_eq!(seoul["geometry"]["type"], "Point");
+        let (latitude, longitude) = data.stations["1호선_037"].coordinates();
+        assert_eq!(
+            seoul["geometry"]["coordinates"],
+            json!([longitude, latitude])
+        );
+        assert!(seoul["properties"]["lines"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("4호선")));
+
+        // 잠실 to 강남 is one leg on 2호선
+        let (from, to) = (&data.stations["2호선_016"], &data.stations["2호선_022"]);
+        let path = dijkstra(&data.graph, data.entrance(from), data.exit(to))
+            .unwrap()
+            .unwrap();
+        let route = route(&Itinerary::from_path(&data.graph, &path));
+        assert_eq!(route.len(), 1);
+        assert_eq!(route[0]["geometry"]["type"], "LineString");
+        assert_eq!(
+            route[0]["geometry"]["coordinates"]
+                .as_array()
+                .unwrap()
+                .len(),
+            7
+        );
+        assert_eq!(route[0]["properties"]["line"], "2호선");
+        assert_eq!(route[0]["properties"]["to"], "강남");
+
+        let collection = feature_collection(features.into_iter().chain(route).collect());
+        assert_eq!(collection["type"], "FeatureCollection");
+        assert_eq!(
+            count(collection["features"].as_array().unwrap(), "route"),
+            1
+        );
+    }
+}
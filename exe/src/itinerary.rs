@@ -0,0 +1,307 @@
+use std::fmt;
+
+use my_trash_bin_240704_lib::graph::{Graph, GraphDistanceF32, GraphEdge};
+
+use crate::{
+    data::{Direction, Hop, Line, RouteNode, Station},
+    timetable::Time,
+};
+
+/// Uninterrupted ride on one line
+#[derive(PartialEq)]
+pub struct Leg {
+    pub line: Line,
+    pub direction: Direction,
+    /// every station of the leg, including where it is boarded and left
+    pub stations: Vec<Station>,
+    pub distance: f32,
+    /// when the train leaves the first station and reaches the last one, if known
+    pub departure: Option<Time>,
+    pub arrival: Option<Time>,
+}
+
+impl Leg {
+    pub fn stops(&self) -> usize {
+        self.stations.len() - 1
+    }
+}
+
+#[derive(PartialEq)]
+pub struct Itinerary {
+    pub legs: Vec<Leg>,
+}
+
+impl Itinerary {
+    pub fn from_path(
+        graph: &Graph<RouteNode, GraphDistanceF32, Hop>,
+        path: &[GraphEdge<GraphDistanceF32, Hop>],
+    ) -> Itinerary {
+        let mut legs = Vec::<Leg>::new();
+        let mut current: Option<Leg> = None;
+        for edge in path {
+            match edge.payload {
+                Hop::Ride { line, direction } => {
+                    let leg = current.get_or_insert_with(|| Leg {
+                        line: line.clone(),
+                        direction: *direction,
+                        stations: vec![graph[edge.from].station.clone()],
+                        distance: 0f32,
+                        departure: None,
+                        arrival: None,
+                    });
+                    // edges are weighed by the routing objective, which need not be km
+                    let (from, to) = (&graph[edge.from].station, &graph[edge.to].station);
+                    leg.distance += from.distance_to(to);
+                    leg.stations.push(to.clone());
+                }
+                _ => legs.extend(current.take()),
+            }
+        }
+        legs.extend(current);
+        Itinerary { legs }
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.legs.iter().fold(0f32, |sum, leg| sum + leg.distance)
+    }
+
+    pub fn transfers(&self) -> usize {
+        self.legs.len().saturating_sub(1)
+    }
+
+    pub fn stops(&self) -> usize {
+        self.legs.iter().map(Leg::stops).sum()
+    }
+}
+
+impl fmt::Display for Itinerary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, leg) in self.legs.iter().enumerate() {
+            let first = leg.stations.first().unwrap();
+            let last = leg.stations.last().unwrap();
+            if index != 0 {
+                writeln!(f, "Transfer to {} at {}", leg.line.name(), first.name())?;
+            }
+            let toward = match leg.line.terminal(last, leg.direction) {
+                Some(terminal) => format!(" toward {}", terminal.name()),
+                None => String::new(),
+            };
+            let times = match (leg.departure, leg.arrival) {
+                (Some(departure), Some(arrival)) => format!(", {} - {}", departure, arrival),
+                _ => String::new(),
+            };
+            writeln!(
+                f,
+                "Take {}{} from {} to {} ({} {}, {:.1} km{})",
+                leg.line.name(),
+                toward,
+                first.name(),
+                last.name(),
+                leg.stops(),
+                if leg.stops() == 1 { "stop" } else { "stops" },
+                leg.distance,
+                times
+            )?;
+            if leg.stations.len() > 2 {
+                writeln!(
+                    f,
+                    "  via {}",
+                    leg.stations[1..leg.stations.len() - 1]
+                        .iter()
+                        .map(Station::name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )?;
+            }
+        }
+        write!(
+            f,
+            "Total {:.1} km, {} stops, {} {}",
+            self.distance(),
+            self.stops(),
+            self.transfers(),
+            if self.transfers() == 1 {
+                "transfer"
+            } else {
+                "transfers"
+            }
+        )?;
+        if let Some(arrival) = self.legs.last().and_then(|leg| leg.arrival) {
+            write!(f, ", arriving at {}", arrival)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use my_trash_bin_240704_lib::{dijkstra, graph::NodeId};
+
+    use crate::data::{build_data, parse_raw, Data, RouteNodeKind, RoutingOptions};
+
+    use super::*;
+
+    /// Line a runs north through A1, Hub and A3, line b east through B1, Hub and B3;
+    /// stations are 0.01 degrees apart
+    fn data() -> Data {
+        let station = |id: &str, name: &str, previous: Option<&str>, next: Option<&str>| {
+            let (latitude, longitude) = match name {
+                "A1" => (37.50, 127.00),
+                "A3" => (37.52, 127.00),
+                "B1" => (37.51, 126.99),
+                "B3" => (37.51, 127.01),
+                _ => (37.51, 127.00),
+            };
+            let transfer = match id {
+                "a_2" => vec!["b_2"],
+                "b_2" => vec!["a_2"],
+                _ => vec![],
+            };
+            serde_json::json!({
+                "id": id,
+                "name": name,
+                "line": &id[..1],
+                "previousStationId": previous,
+                "nextStationId": next,
+                "transferStationIds": transfer,
+                "latitude": latitude,
+                "longitude": longitude,
+            })
+        };
+        let stations = serde_json::json!([
+            station("a_1", "A1", None, Some("a_2")),
+            station("a_2", "Hub", Some("a_1"), Some("a_3")),
+            station("a_3", "A3", Some("a_2"), None),
+            station("b_1", "B1", None, Some("b_2")),
+            station("b_2", "Hub", Some("b_1"), Some("b_3")),
+            station("b_3", "B3", Some("b_2"), None),
+        ]);
+        let raw = parse_raw(&serde_json::to_vec(&stations).unwrap()).unwrap();
+        build_data(raw, &RoutingOptions::default()).unwrap()
+    }
+
+    fn platform(data: &Data, station: &str, line: &str, direction: Direction) -> NodeId {
+        let station = &data.stations[station];
+        data.platforms(station)
+            .into_iter()
+            .find(|&node| {
+                matches!(&data.graph[node].kind, RouteNodeKind::Platform(platform_line, platform_direction)
+                    if platform_line.name() == line && *platform_direction == direction)
+            })
+            .unwrap()
+    }
+
+    /// The edges joining `nodes` one after the other
+    fn path<'a>(data: &'a Data, nodes: &[NodeId]) -> Vec<GraphEdge<'a, GraphDistanceF32, Hop>> {
+        nodes
+            .windows(2)
+            .map(|pair| {
+                data.graph
+                    .adjacent(pair[0])
+                    .find(|edge| edge.to == pair[1])
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn route(data: &Data, from: &str, to: &str) -> Itinerary {
+        let (from, to) = (&data.stations[from], &data.stations[to]);
+        let path = dijkstra(&data.graph, data.entrance(from), data.exit(to))
+            .unwrap()
+            .unwrap();
+        Itinerary::from_path(&data.graph, &path)
+    }
+
+    #[test]
+    fn one_leg() {
+        let data = data();
+        let itinerary = route(&data, "a_1", "a_3");
+        assert_eq!(itinerary.legs.len(), 1);
+        assert_eq!(itinerary.legs[0].direction, Direction::Down);
+        assert_eq!(
+            itinerary.legs[0]
+                .stations
+                .iter()
+                .map(Station::name)
+                .collect::<Vec<_>>(),
+            vec!["A1", "Hub", "A3"]
+        );
+        assert_eq!((itinerary.stops(), itinerary.transfers()), (2, 0));
+        let km = data.stations["a_1"].distance_to(&data.stations["a_3"]);
+        assert!((itinerary.distance() - km).abs() < 1e-3);
+        assert_eq!(
+            itinerary.to_string(),
+            "Take a toward A3 from A1 to A3 (2 stops, 2.2 km)\n  \
+             via Hub\n\
+             Total 2.2 km, 2 stops, 0 transfers"
+        );
+    }
+
+    #[test]
+    fn transfer_closes_a_leg() {
+        let data = data();
+        let itinerary = route(&data, "a_1", "b_3");
+        assert_eq!(itinerary.legs.len(), 2);
+        assert_eq!((itinerary.stops(), itinerary.transfers()), (2, 1));
+        assert_eq!(
+            itinerary.to_string(),
+            "Take a toward A3 from A1 to Hub (1 stop, 1.1 km)\n\
+             Transfer to b at Hub\n\
+             Take b toward B3 from Hub to B3 (1 stop, 0.9 km)\n\
+             Total 2.0 km, 2 stops, 1 transfer"
+        );
+
+        // changing platforms before riding leaves no empty leg
+        let (hub, b3) = (&data.stations["a_2"], &data.stations["b_3"]);
+        let nodes = [
+            data.entrance(hub),
+            platform(&data, "a_2", "a", Direction::Down),
+            platform(&data, "a_2", "b", Direction::Down),
+            platform(&data, "b_3", "b", Direction::Down),
+            data.exit(b3),
+        ];
+        let itinerary = Itinerary::from_path(&data.graph, &path(&data, &nodes));
+        assert_eq!(itinerary.legs.len(), 1);
+        assert_eq!(itinerary.legs[0].line.name(), "b");
+        assert_eq!(itinerary.transfers(), 0);
+    }
+
+    #[test]
+    fn turning_back_starts_a_leg() {
+        let data = data();
+        let a1 = &data.stations["a_1"];
+        let nodes = [
+            data.entrance(a1),
+            platform(&data, "a_1", "a", Direction::Down),
+            platform(&data, "a_2", "a", Direction::Down),
+            platform(&data, "a_2", "a", Direction::Up),
+            platform(&data, "a_1", "a", Direction::Up),
+            data.exit(a1),
+        ];
+        let itinerary = Itinerary::from_path(&data.graph, &path(&data, &nodes));
+        assert_eq!(
+            itinerary
+                .legs
+                .iter()
+                .map(|leg| leg.direction)
+                .collect::<Vec<_>>(),
+            vec![Direction::Down, Direction::Up]
+        );
+        assert_eq!((itinerary.stops(), itinerary.transfers()), (2, 1));
+        assert_eq!(
+            itinerary.to_string(),
+            "Take a toward A3 from A1 to Hub (1 stop, 1.1 km)\n\
+             Transfer to a at Hub\n\
+             Take a toward A1 from Hub to A1 (1 stop, 1.1 km)\n\
+             Total 2.2 km, 2 stops, 1 transfer"
+        );
+    }
+
+    #[test]
+    fn no_ride() {
+        let data = data();
+        let itinerary = route(&data, "a_2", "b_2");
+        assert!(itinerary.legs.is_empty());
+        assert_eq!(itinerary.to_string(), "Total 0.0 km, 0 stops, 0 transfers");
+    }
+}
@@ -1,49 +1,644 @@
-use std::{env, error::Error};
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::Path,
+    process::ExitCode,
+};
 
-use data::{parse_data, Station};
+use cli::{parse_args, Cli, Command, ExportFormat, USAGE};
+use data::{
+    build_data,
+    lookup::{MatchKind, StationIndex},
+    parse_raw,
+    scenario::{route_changes, Scenario},
+    Avoid, Data, Direction, Hop, Objective, RouteNode, RouteNodeKind, RoutingOptions, Station,
+    StationRaw,
+};
+use itinerary::Itinerary;
 use my_trash_bin_240704_lib::{
-    dijkstra,
-    graph::{Graph, GraphDistanceF32, GraphNode},
+    astar, astar_filtered,
+    batch::{available_threads, dijkstra_batch, parallel_map},
+    export::{to_dot, to_graphml},
+    graph::{GraphDistanceF32, GraphEdge},
+    k_shortest::shortest_paths_filtered,
+    pareto::{pareto_paths, CostVector},
+    shortest_path_tree, shortest_path_tree_filtered,
+    via::{via_path, via_path_unordered},
+    NegativeDistance,
 };
+use timetable::{Schedule, Time};
 
+mod cli;
 mod data;
+mod geojson;
+mod itinerary;
+mod timetable;
+
+/// Paths examined per requested alternative before giving up; many paths only differ in
+/// where a transfer happens inside a station and print the same itinerary
+const PATHS_PER_ALTERNATIVE: usize = 20;
+
+/// Resolves the closed stations and suspended lines given on the command line
+fn avoid(data: &Data, stations: &[String], lines: &[String]) -> Result<Avoid, Box<dyn Error>> {
+    let index = StationIndex::new(data);
+    Ok(Avoid {
+        stations: stations
+            .iter()
+            .map(|station| index.resolve(station))
+            .collect::<Result<_, _>>()?,
+        lines: lines
+            .iter()
+            .map(|line| {
+                data.lines
+                    .get(line)
+                    .cloned()
+                    .ok_or_else(|| format!("Unknown line: {}", line))
+            })
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// Fails if `station` is closed, as no route can start or end there
+fn check_open(station: &Station, avoid: &Avoid) -> Result<(), Box<dyn Error>> {
+    if avoid.stations.contains(station) {
+        return Err(format!("Station {} is closed", station.name()).into());
+    }
+    Ok(())
+}
+
+fn route(
+    data: &Data,
+    from: &str,
+    to: &str,
+    alternatives: Option<usize>,
+    pareto: bool,
+    avoid: &Avoid,
+) -> Result<(), Box<dyn Error>> {
+    let index = StationIndex::new(data);
+    let from = index.resolve(from)?;
+    let to = index.resolve(to)?;
+    check_open(&from, avoid)?;
+    check_open(&to, avoid)?;
+
+    if pareto {
+        let front = pareto_paths(&data.graph, data.entrance(&from), data.exit(&to), |edge| {
+            match edge.payload {
+                // distance in m, transfers, stops
+                Hop::Ride { .. } => {
+                    CostVector([(data.ride_distance(edge) * 1000.0).round() as u32, 0, 1])
+                }
+                Hop::Transfer { .. } => CostVector([0, 1, 0]),
+                Hop::Board(_) | Hop::Alight(_) => CostVector([0, 0, 0]),
+            }
+        })?;
+        let itineraries = front
+            .iter()
+            .map(|(_, path)| Itinerary::from_path(&data.graph, path))
+            .collect::<Vec<_>>();
+        print_options(&itineraries);
+        return Ok(());
+    }
 
-fn find_node(
-    graph: &Graph<Station, GraphDistanceF32>,
-    id: &String,
-) -> Option<GraphNode<Station, GraphDistanceF32>> {
-    for index in 0..graph.length() {
-        let node = graph[index].clone();
-        if node.value().has_id(id) {
-            return Some(node);
+    let Some(alternatives) = alternatives else {
+        let path = astar_filtered(
+            &data.graph,
+            data.entrance(&from),
+            data.exit(&to),
+            data.heuristic(&to),
+            avoid.nodes(data),
+            avoid.hops(data),
+        )?;
+        match path {
+            None => println!("No way"),
+            Some(path) => println!("{}", Itinerary::from_path(&data.graph, &path)),
+        }
+        return Ok(());
+    };
+
+    let mut itineraries = Vec::<Itinerary>::new();
+    let paths = shortest_paths_filtered(
+        &data.graph,
+        data.entrance(&from),
+        data.exit(&to),
+        avoid.nodes(data),
+        avoid.hops(data),
+    )?;
+    for path in paths.take(alternatives * PATHS_PER_ALTERNATIVE) {
+        let itinerary = Itinerary::from_path(&data.graph, &path);
+        if !itineraries.contains(&itinerary) {
+            itineraries.push(itinerary);
+            if itineraries.len() == alternatives {
+                break;
+            }
         }
     }
-    None
+    print_options(&itineraries);
+    Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    // let args = env::args().collect::<Vec<_>>();
+/// Shortest route from `from` to `to` passing every station of `via`, in order unless
+/// `any_order`
+fn via_route(
+    data: &Data,
+    from: &str,
+    to: &str,
+    via: &[String],
+    any_order: bool,
+) -> Result<(), Box<dyn Error>> {
+    let index = StationIndex::new(data);
+    let from = index.resolve(from)?;
+    let to = index.resolve(to)?;
+    let waypoints = via
+        .iter()
+        .map(|station| Ok(data.platforms(&index.resolve(station)?)))
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
 
-    let data: String = std::fs::read_to_string("data.json")?;
-    // let data: String = std::fs::read_to_string(args[1])?;
-    let data = parse_data(data.as_bytes())?;
+    let (from, to) = (data.entrance(&from), data.exit(&to));
+    let path = if any_order {
+        via_path_unordered(&data.graph, from, &waypoints, to)?
+    } else {
+        via_path(&data.graph, from, &waypoints, to)?
+    };
+    match path {
+        None => println!("No way"),
+        Some(path) => println!("{}", Itinerary::from_path(&data.graph, &path)),
+    }
+    Ok(())
+}
 
-    let start = find_node(&data.graph, &"에버라인선_015".to_string()).unwrap();
-    let end = find_node(&data.graph, &"5호선_020".to_string()).unwrap();
+fn print_options(itineraries: &[Itinerary]) {
+    if itineraries.is_empty() {
+        println!("No way");
+    }
+    for (index, itinerary) in itineraries.iter().enumerate() {
+        if index != 0 {
+            println!();
+        }
+        println!(
+            "Option {}: {:.1} km, {} {}, {} {}",
+            index + 1,
+            itinerary.distance(),
+            itinerary.transfers(),
+            if itinerary.transfers() == 1 {
+                "transfer"
+            } else {
+                "transfers"
+            },
+            itinerary.stops(),
+            if itinerary.stops() == 1 {
+                "stop"
+            } else {
+                "stops"
+            }
+        );
+        println!("{}", itinerary);
+    }
+}
 
-    match dijkstra(start, end) {
+fn timed_route(
+    data: &Data,
+    from: &str,
+    to: &str,
+    departure: Time,
+    timetable: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let index = StationIndex::new(data);
+    let from = index.resolve(from)?;
+    let to = index.resolve(to)?;
+
+    let services = match timetable {
+        Some(path) => serde_json::from_slice(
+            &fs::read(path)
+                .map_err(|error| format!("Failed to read {}: {}", path.display(), error))?,
+        )
+        .map_err(|error| format!("Invalid timetable {}: {}", path.display(), error))?,
+        None => HashMap::new(),
+    };
+    match Schedule::new(data, &services)?.route(&from, &to, departure) {
         None => println!("No way"),
-        Some(vec) => {
-            for edge in vec.into_iter() {
+        Some(itinerary) => println!("{}", itinerary),
+    }
+    Ok(())
+}
+
+fn stations(data: &Data, line: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let stations = match line {
+        Some(line) => data
+            .lines
+            .get(line)
+            .ok_or_else(|| format!("Unknown line: {}", line))?
+            .stations(),
+        None => {
+            let mut stations = data.stations.values().cloned().collect::<Vec<_>>();
+            stations.sort_by_key(Station::id);
+            stations.dedup();
+            stations
+        }
+    };
+    for station in stations {
+        println!("{}\t{}", station.id(), station.name());
+    }
+    Ok(())
+}
+
+fn lines(data: &Data) {
+    let mut lines = data.lines.values().collect::<Vec<_>>();
+    lines.sort_by_key(|line| (line.name(), line.id()));
+    for line in lines {
+        // --line and the other options naming a line take its id
+        let name = if line.id() == line.name() {
+            line.name()
+        } else {
+            format!("{} ({})", line.name(), line.id())
+        };
+        println!("{}\t{} stations", name, line.stations().len());
+    }
+}
+
+fn info(data: &Data, query: &str) -> Result<(), Box<dyn Error>> {
+    let station = StationIndex::new(data).resolve(query)?;
+    let (latitude, longitude) = station.coordinates();
+    println!("{}", station.name());
+    println!("  ids: {}", station.ids().join(", "));
+    println!("  coordinates: {}, {}", latitude, longitude);
+    for (line, previous, next) in station.lines() {
+        println!(
+            "  {}: {} <- -> {}",
+            line.name(),
+            previous.map_or("-".to_string(), |station| station.name()),
+            next.map_or("-".to_string(), |station| station.name())
+        );
+    }
+    Ok(())
+}
+
+fn search(data: &Data, query: &str) {
+    for candidate in StationIndex::new(data).search(query) {
+        println!(
+            "{}\t{}\t{}",
+            candidate.station.id(),
+            candidate.station.name(),
+            match candidate.kind {
+                MatchKind::Id => "id",
+                MatchKind::Name => "name",
+                MatchKind::Prefix => "prefix",
+                MatchKind::Substring => "substring",
+                MatchKind::Choseong => "initial consonants",
+                MatchKind::Romanization => "romanization",
+            }
+        );
+    }
+}
+
+fn batch(data: &Data, input: &str, threads: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let input = if input == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(input).map_err(|error| format!("Failed to read {}: {}", input, error))?
+    };
+    let index = StationIndex::new(data);
+    let mut queries = Vec::new();
+    for (number, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (from, to) = line
+            .split_once([',', '\t'])
+            .ok_or_else(|| format!("Line {}: expected <from>,<to>", number + 1))?;
+        let resolve = |query: &str| {
+            index
+                .resolve(query.trim())
+                .map_err(|error| format!("Line {}: {}", number + 1, error))
+        };
+        queries.push((resolve(from)?, resolve(to)?));
+    }
+
+    let nodes = queries
+        .iter()
+        .map(|(from, to)| (data.entrance(from), data.exit(to)))
+        .collect::<Vec<_>>();
+    let paths = dijkstra_batch(
+        &data.graph,
+        &nodes,
+        threads.unwrap_or_else(available_threads),
+    )?;
+
+    println!("from,to,distance_km,stops,transfers");
+    for ((from, to), path) in queries.iter().zip(paths) {
+        match path {
+            Some(path) => {
+                let itinerary = Itinerary::from_path(&data.graph, &path);
                 println!(
-                    "{} to {} ({} km)",
-                    edge.from.value().name(),
-                    edge.to.value().name(),
-                    *edge.distance
-                )
+                    "{},{},{:.3},{},{}",
+                    from.id(),
+                    to.id(),
+                    itinerary.distance(),
+                    itinerary.stops(),
+                    itinerary.transfers()
+                );
             }
+            None => println!("{},{},,,", from.id(), to.id()),
         }
     }
+    Ok(())
+}
 
+fn distances(data: &Data, from: &str, avoid: &Avoid) -> Result<(), Box<dyn Error>> {
+    let from = StationIndex::new(data).resolve(from)?;
+    check_open(&from, avoid)?;
+    let tree = shortest_path_tree_filtered(
+        &data.graph,
+        data.entrance(&from),
+        avoid.nodes(data),
+        avoid.hops(data),
+    )?;
+    let mut out = io::stdout().lock();
+    for node in tree
+        .reachable()
+        .filter(|&node| data.graph[node].kind == RouteNodeKind::Exit)
+    {
+        let path = tree.path_to(&data.graph, node).unwrap();
+        let itinerary = Itinerary::from_path(&data.graph, &path);
+        let station = &data.graph[node].station;
+        writeln!(
+            out,
+            "{}\t{}\t{:.1} km\t{} stops\t{} transfers",
+            station.id(),
+            station.name(),
+            itinerary.distance(),
+            itinerary.stops(),
+            itinerary.transfers()
+        )?;
+    }
+    Ok(())
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn table(data: &Data, threads: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let mut stations = data.stations.values().cloned().collect::<Vec<_>>();
+    stations.sort_by_key(Station::id);
+    stations.dedup();
+    let rows = parallel_map(
+        &stations,
+        threads.unwrap_or_else(available_threads),
+        |from| {
+            let tree = shortest_path_tree(&data.graph, data.entrance(from))?;
+            // km ridden to every node of the tree, parents first
+            let mut km = vec![0f32; data.graph.length()];
+            for node in tree.reachable() {
+                if let Some(edge) = tree.predecessor(node) {
+                    let edge = data.graph.edge(edge);
+                    km[node.index()] = km[edge.from.index()]
+                        + match edge.payload {
+                            Hop::Ride { .. } => data.ride_distance(&edge),
+                            _ => 0f32,
+                        };
+                }
+            }
+            Ok::<_, NegativeDistance>(
+                stations
+                    .iter()
+                    .map(|to| {
+                        let exit = data.exit(to);
+                        tree.distance(exit)
+                            .map_or(String::new(), |_| format!("{:.3}", km[exit.index()]))
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        },
+    );
+
+    let mut out = io::stdout().lock();
+    let header = stations
+        .iter()
+        .map(|station| csv_field(&station.name()))
+        .collect::<Vec<_>>();
+    writeln!(out, ",{}", header.join(","))?;
+    for (from, row) in stations.iter().zip(rows) {
+        writeln!(out, "{},{}", csv_field(&from.name()), row?.join(","))?;
+    }
+    Ok(())
+}
+
+fn export(
+    data: &Data,
+    format: ExportFormat,
+    route: Option<(String, String)>,
+) -> Result<(), Box<dyn Error>> {
+    let node_label = |_, node: &RouteNode| match &node.kind {
+        RouteNodeKind::Entrance => format!("{} entrance", node.station.name()),
+        RouteNodeKind::Exit => format!("{} exit", node.station.name()),
+        RouteNodeKind::Platform(line, direction) => format!(
+            "{} {} {}",
+            node.station.name(),
+            line.name(),
+            match direction {
+                Direction::Up => "up",
+                Direction::Down => "down",
+            }
+        ),
+    };
+    let edge_label = |edge: &GraphEdge<'_, GraphDistanceF32, Hop>| match edge.payload {
+        Hop::Board(_) => "board".to_string(),
+        Hop::Alight(_) => "alight".to_string(),
+        Hop::Ride { line, .. } => format!("{} {:.2} km", line.name(), data.ride_distance(edge)),
+        Hop::Transfer { .. } => "transfer".to_string(),
+    };
+
+    let mut out = io::stdout().lock();
+    match format {
+        ExportFormat::Dot => write!(out, "{}", to_dot(&data.graph, node_label, edge_label))?,
+        ExportFormat::GraphMl => {
+            write!(out, "{}", to_graphml(&data.graph, node_label, edge_label))?
+        }
+        ExportFormat::GeoJson => {
+            let mut features = geojson::network(data);
+            if let Some((from, to)) = route {
+                let index = StationIndex::new(data);
+                let from = index.resolve(&from)?;
+                let to = index.resolve(&to)?;
+                let path = astar(
+                    &data.graph,
+                    data.entrance(&from),
+                    data.exit(&to),
+                    data.heuristic(&to),
+                )?
+                .ok_or_else(|| format!("No way from {} to {}", from.name(), to.name()))?;
+                features.extend(geojson::route(&Itinerary::from_path(&data.graph, &path)));
+            }
+            writeln!(out, "{}", geojson::feature_collection(features))?;
+        }
+    }
+    Ok(())
+}
+
+fn changes(
+    before: &Data,
+    after: &Data,
+    threads: Option<usize>,
+    objective: Objective,
+) -> Result<(), Box<dyn Error>> {
+    let changes = route_changes(before, after, threads.unwrap_or_else(available_threads))?;
+    let cost = |cost: Option<f32>| cost.map_or("-".to_string(), |cost| objective.format_cost(cost));
+    for change in changes.iter() {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            change.from.id(),
+            change.from.name(),
+            change.to.id(),
+            change.to.name(),
+            cost(change.before),
+            cost(change.after)
+        );
+    }
+    eprintln!("{} routes change", changes.len());
     Ok(())
 }
+
+fn validate(raw: &[StationRaw]) -> Result<(), Box<dyn Error>> {
+    let report = data::validate::validate(raw);
+    println!("{}", report);
+    match report.errors() {
+        0 => Ok(()),
+        errors => Err(format!("Station data has {} errors", errors).into()),
+    }
+}
+
+fn run(
+    Cli {
+        data,
+        gtfs,
+        transfer_penalty,
+        optimize,
+        timetable,
+        avoid_stations,
+        avoid_lines,
+        scenario,
+        command,
+    }: Cli,
+) -> Result<(), Box<dyn Error>> {
+    if command == Command::Help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let mut options = RoutingOptions::default();
+    if let Some(transfer_penalty) = transfer_penalty {
+        options.transfer_penalty = transfer_penalty;
+    }
+    if let Some(objective) = optimize {
+        options.objective = objective;
+    }
+    let raw = match gtfs {
+        Some(gtfs) => {
+            let (raw, dropped) = data::gtfs::load(&gtfs)
+                .map_err(|error| format!("Failed to load {}: {}", gtfs.display(), error))?;
+            for link in dropped {
+                eprintln!("warning: {}", link);
+            }
+            raw
+        }
+        None => {
+            let bytes = fs::read(&data)
+                .map_err(|error| format!("Failed to read {}: {}", data.display(), error))?;
+            parse_raw(&bytes)?
+        }
+    };
+    let applied = match scenario {
+        Some(path) => {
+            let bytes = fs::read(&path)
+                .map_err(|error| format!("Failed to read {}: {}", path.display(), error))?;
+            Some(Scenario::parse(&bytes)?.apply(&raw)?)
+        }
+        None => None,
+    };
+    if command == Command::Validate {
+        return validate(applied.as_deref().unwrap_or(&raw));
+    }
+    if let Command::Changes { threads } = command {
+        let after = applied.ok_or("changes requires --scenario")?;
+        return changes(
+            &build_data(raw, &options)?,
+            &build_data(after, &options)?,
+            threads,
+            options.objective,
+        );
+    }
+    let data = build_data(applied.unwrap_or(raw), &options)?;
+    let avoid = avoid(&data, &avoid_stations, &avoid_lines)?;
+
+    match command {
+        Command::Route {
+            from,
+            to,
+            via,
+            any_order,
+            ..
+        } if !via.is_empty() => via_route(&data, &from, &to, &via, any_order),
+        Command::Route {
+            from,
+            to,
+            depart: Some(departure),
+            ..
+        } => timed_route(&data, &from, &to, departure, timetable.as_deref()),
+        Command::Route {
+            from,
+            to,
+            alternatives,
+            pareto,
+            depart: None,
+            ..
+        } => route(&data, &from, &to, alternatives, pareto, &avoid),
+        Command::Stations { line } => stations(&data, line.as_deref()),
+        Command::Lines => {
+            lines(&data);
+            Ok(())
+        }
+        Command::Info { station } => info(&data, &station),
+        Command::Batch { input, threads } => batch(&data, &input, threads),
+        Command::Distances { from } => distances(&data, &from, &avoid),
+        Command::Export { format, route } => export(&data, format, route),
+        Command::Table { threads } => table(&data, threads),
+        Command::Search { query } => {
+            search(&data, &query);
+            Ok(())
+        }
+        Command::Changes { .. } | Command::Validate | Command::Help => unreachable!(),
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = match parse_args(env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        // whoever reads the output stopped early, as `head` does
+        Err(error)
+            if error
+                .downcast_ref::<io::Error>()
+                .is_some_and(|error| error.kind() == io::ErrorKind::BrokenPipe) =>
+        {
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
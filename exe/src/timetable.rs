@@ -0,0 +1,415 @@
+use std::{collections::HashMap, error::Error, fmt};
+
+use my_trash_bin_240704_lib::connection_scan::{Connection, InvalidConnection, Timetable};
+
+use crate::{
+    data::{Data, Direction, Line, Station},
+    itinerary::{Itinerary, Leg},
+};
+
+/// Time of day in seconds since midnight, past 24:00 for trips running after midnight
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Time(pub u32);
+
+impl Time {
+    /// Parses `HH:MM`
+    pub fn parse(value: &str) -> Option<Time> {
+        let (hours, minutes) = value.split_once(':')?;
+        if minutes.len() != 2 {
+            return None;
+        }
+        let hours = hours.parse::<u32>().ok().filter(|hours| *hours < 48)?;
+        let minutes = minutes
+            .parse::<u32>()
+            .ok()
+            .filter(|minutes| *minutes < 60)?;
+        Some(Time((hours * 60 + minutes) * 60))
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.0 / 3600, self.0 / 60 % 60)
+    }
+}
+
+/// Reason the service of a timetable file is unusable
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduleError {
+    UnknownLine(String),
+    InvalidTime {
+        line: String,
+        value: String,
+    },
+    InvalidHeadway {
+        line: String,
+        minutes: f32,
+    },
+    InvalidSpeed {
+        line: String,
+        kmh: f32,
+    },
+    /// last departure earlier than the first one
+    InvalidService {
+        line: String,
+        first: Time,
+        last: Time,
+    },
+    InvalidConnection(InvalidConnection),
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::UnknownLine(line) => write!(f, "Unknown line in timetable: {}", line),
+            ScheduleError::InvalidTime { line, value } => {
+                write!(f, "Invalid time for {}: {}", line, value)
+            }
+            ScheduleError::InvalidHeadway { line, minutes } => {
+                write!(f, "Invalid headway for {}: {}", line, minutes)
+            }
+            ScheduleError::InvalidSpeed { line, kmh } => {
+                write!(f, "Invalid speed for {}: {}", line, kmh)
+            }
+            ScheduleError::InvalidService { line, first, last } => write!(
+                f,
+                "Last departure of {} at {} is before its first departure at {}",
+                line, last, first
+            ),
+            ScheduleError::InvalidConnection(connection) => {
+                write!(f, "Invalid timetable: {}", connection)
+            }
+        }
+    }
+}
+
+impl Error for ScheduleError {}
+
+/// Service of one line as given in a timetable file, every field optional
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct LineServiceRaw {
+    headway_minutes: Option<f32>,
+    speed_kmh: Option<f32>,
+    first_departure: Option<String>,
+    last_departure: Option<String>,
+}
+
+/// Trains leaving both ends of a line every `headway` from `first_departure` to `last_departure`;
+/// on a circular line they run round until `last_departure` instead
+struct LineService {
+    headway: u32,
+    speed_kmh: f32,
+    first_departure: Time,
+    last_departure: Time,
+}
+
+pub(crate) const DEFAULT_HEADWAY_MINUTES: f32 = 6.0;
+pub(crate) const DEFAULT_SPEED_KMH: f32 = 34.0;
+const DEFAULT_FIRST_DEPARTURE: Time = Time(5 * 3600 + 30 * 60);
+const DEFAULT_LAST_DEPARTURE: Time = Time(24 * 3600);
+/// Seconds a train stands at every intermediate station
+pub(crate) const DWELL_TIME: u32 = 30;
+/// Seconds needed to walk between two platforms of a station
+pub(crate) const TRANSFER_TIME: u32 = 240;
+
+impl LineService {
+    fn new(line: &str, raw: &LineServiceRaw) -> Result<LineService, ScheduleError> {
+        let time = |value: &Option<String>, default: Time| match value {
+            Some(value) => Time::parse(value).ok_or_else(|| ScheduleError::InvalidTime {
+                line: line.to_string(),
+                value: value.clone(),
+            }),
+            None => Ok(default),
+        };
+        let headway_minutes = raw.headway_minutes.unwrap_or(DEFAULT_HEADWAY_MINUTES);
+        let speed_kmh = raw.speed_kmh.unwrap_or(DEFAULT_SPEED_KMH);
+        if !(headway_minutes >= 1.0 && headway_minutes.is_finite()) {
+            return Err(ScheduleError::InvalidHeadway {
+                line: line.to_string(),
+                minutes: headway_minutes,
+            });
+        }
+        if !(speed_kmh > 0.0 && speed_kmh.is_finite()) {
+            return Err(ScheduleError::InvalidSpeed {
+                line: line.to_string(),
+                kmh: speed_kmh,
+            });
+        }
+        let first_departure = time(&raw.first_departure, DEFAULT_FIRST_DEPARTURE)?;
+        let last_departure = time(&raw.last_departure, DEFAULT_LAST_DEPARTURE)?;
+        if last_departure < first_departure {
+            return Err(ScheduleError::InvalidService {
+                line: line.to_string(),
+                first: first_departure,
+                last: last_departure,
+            });
+        }
+        Ok(LineService {
+            headway: (headway_minutes * 60.0).round() as u32,
+            speed_kmh,
+            first_departure,
+            last_departure,
+        })
+    }
+}
+
+/// Stations every train of a line visits in one direction, round as many laps as the longest
+/// running train on a circular line
+struct Pattern {
+    line: Line,
+    direction: Direction,
+    stations: Vec<Station>,
+}
+
+/// Trains of every line, generated from their services
+pub struct Schedule {
+    /// payload is the index in its pattern's `stations` of the station a connection leaves
+    timetable: Timetable<usize>,
+    patterns: Vec<Pattern>,
+    /// pattern of every trip
+    trips: Vec<usize>,
+    stops: HashMap<String, usize>,
+}
+
+impl Schedule {
+    /// Runs every line with the service in `services`, or the default one
+    pub fn new(
+        data: &Data,
+        services: &HashMap<String, LineServiceRaw>,
+    ) -> Result<Schedule, ScheduleError> {
+        if let Some(unknown) = services.keys().find(|line| !data.lines.contains_key(*line)) {
+            return Err(ScheduleError::UnknownLine(unknown.clone()));
+        }
+        let mut stations = data.stations.values().cloned().collect::<Vec<_>>();
+        stations.sort_by_key(Station::id);
+        stations.dedup();
+        let stops = stations
+            .iter()
+            .enumerate()
+            .flat_map(|(index, station)| station.ids().into_iter().map(move |id| (id, index)))
+            .collect::<HashMap<_, _>>();
+
+        let mut lines = data.lines.values().collect::<Vec<_>>();
+        lines.sort_by_key(|line| line.id());
+        let mut patterns = Vec::new();
+        let mut trips = Vec::new();
+        let mut connections = Vec::new();
+        for line in lines {
+            let service = LineService::new(
+                &line.id(),
+                services
+                    .get(&line.id())
+                    .unwrap_or(&LineServiceRaw::default()),
+            )?;
+            for direction in [Direction::Up, Direction::Down] {
+                for stations in line.runs(direction) {
+                    let running = stations
+                        .windows(2)
+                        .map(|pair| {
+                            (pair[0].distance_to(&pair[1]) / service.speed_kmh * 3600.0).round()
+                                as u32
+                        })
+                        .collect::<Vec<_>>();
+                    // trains on a circular line keep going round until the end of service, so
+                    // only as many leave as fit in one lap
+                    let lap =
+                        (stations.len() > 2 && stations.first() == stations.last()).then(|| {
+                            running
+                                .iter()
+                                .map(|running| running + DWELL_TIME)
+                                .sum::<u32>()
+                        });
+                    let mut laps = 1;
+                    let mut start = service.first_departure.0;
+                    while start <= service.last_departure.0
+                        && lap.is_none_or(|lap| start < service.first_departure.0 + lap)
+                    {
+                        let mut departure = start;
+                        let mut index = 0;
+                        loop {
+                            for (pair, running) in stations.windows(2).zip(&running) {
+                                let arrival = departure + running;
+                                connections.push(Connection {
+                                    from: stops[&pair[0].id()],
+                                    to: stops[&pair[1].id()],
+                                    departure,
+                                    arrival,
+                                    trip: trips.len(),
+                                    payload: index,
+                                });
+                                departure = arrival + DWELL_TIME;
+                                index += 1;
+                            }
+                            if lap.is_none() || departure > service.last_departure.0 {
+                                break;
+                            }
+                        }
+                        laps = laps.max(index / running.len());
+                        trips.push(patterns.len());
+                        start += service.headway;
+                    }
+                    let stations = match lap {
+                        Some(_) => {
+                            let lap = &stations[..stations.len() - 1];
+                            lap.iter()
+                                .cycle()
+                                .take(lap.len() * laps + 1)
+                                .cloned()
+                                .collect()
+                        }
+                        None => stations,
+                    };
+                    patterns.push(Pattern {
+                        line: line.clone(),
+                        direction,
+                        stations,
+                    });
+                }
+            }
+        }
+
+        Ok(Schedule {
+            timetable: Timetable::new(connections, vec![TRANSFER_TIME; stations.len()])
+                .map_err(ScheduleError::InvalidConnection)?,
+            patterns,
+            trips,
+            stops,
+        })
+    }
+
+    /// Itinerary leaving `from` at `departure` or later that reaches `to` the earliest
+    pub fn route(&self, from: &Station, to: &Station, departure: Time) -> Option<Itinerary> {
+        let journey = self.timetable.earliest_arrival(
+            self.stops[&from.id()],
+            self.stops[&to.id()],
+            departure.0,
+        )?;
+        let connections = self.timetable.connections();
+        let legs = journey
+            .into_iter()
+            .map(|leg| {
+                let (first, last) = (&connections[leg.first], &connections[leg.last]);
+                let pattern = &self.patterns[self.trips[first.trip]];
+                let stations = pattern.stations[first.payload..=last.payload + 1].to_vec();
+                Leg {
+                    line: pattern.line.clone(),
+                    direction: pattern.direction,
+                    distance: stations
+                        .windows(2)
+                        .fold(0f32, |sum, pair| sum + pair[0].distance_to(&pair[1])),
+                    stations,
+                    departure: Some(Time(first.departure)),
+                    arrival: Some(Time(last.arrival)),
+                }
+            })
+            .collect();
+        Some(Itinerary { legs })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{build_data, parse_raw, RoutingOptions};
+
+    use super::*;
+
+    #[test]
+    fn parse_and_print_times() {
+        assert_eq!(Time::parse("08:05"), Some(Time(8 * 3600 + 5 * 60)));
+        assert_eq!(
+            Time::parse("8:05").map(|time| time.to_string()),
+            Some("08:05".to_string())
+        );
+        assert_eq!(
+            Time::parse("25:10").map(|time| time.to_string()),
+            Some("25:10".to_string())
+        );
+        assert_eq!(Time::parse("08:60"), None);
+        assert_eq!(Time::parse("0805"), None);
+    }
+
+    #[test]
+    fn timed_route() {
+        let data = build_data(
+            parse_raw(include_bytes!("../../data.json")).unwrap(),
+            &RoutingOptions::default(),
+        )
+        .unwrap();
+        let schedule = Schedule::new(&data, &HashMap::new()).unwrap();
+        let from = &data.stations["2호선_016"];
+        let to = &data.stations["5호선_024"];
+        let departure = Time::parse("08:00").unwrap();
+
+        let itinerary = schedule.route(from, to, departure).unwrap();
+        assert_eq!(itinerary.legs.first().unwrap().stations[0], *from);
+        assert_eq!(itinerary.legs.last().unwrap().stations.last(), Some(to));
+        let mut time = departure;
+        for (index, leg) in itinerary.legs.iter().enumerate() {
+            let (leg_departure, leg_arrival) = (leg.departure.unwrap(), leg.arrival.unwrap());
+            let transfer = if index == 0 { 0 } else { TRANSFER_TIME };
+            assert!(Time(time.0 + transfer) <= leg_departure);
+            assert!(leg_departure < leg_arrival);
+            time = leg_arrival;
+        }
+        assert!(schedule
+            .route(from, to, Time::parse("25:00").unwrap())
+            .is_none());
+
+        let services = HashMap::from([(
+            "2호선".to_string(),
+            LineServiceRaw {
+                speed_kmh: Some(0.0),
+                ..Default::default()
+            },
+        )]);
+        assert_eq!(
+            Schedule::new(&data, &services).err(),
+            Some(ScheduleError::InvalidSpeed {
+                line: "2호선".to_string(),
+                kmh: 0.0
+            })
+        );
+
+        let services = HashMap::from([(
+            "2호선".to_string(),
+            LineServiceRaw {
+                first_departure: Some("23:00".to_string()),
+                last_departure: Some("22:59".to_string()),
+                ..Default::default()
+            },
+        )]);
+        assert_eq!(
+            Schedule::new(&data, &services).err(),
+            Some(ScheduleError::InvalidService {
+                line: "2호선".to_string(),
+                first: Time::parse("23:00").unwrap(),
+                last: Time::parse("22:59").unwrap()
+            })
+        );
+    }
+
+    #[test]
+    fn ride_across_the_start_of_a_circular_line() {
+        let data = build_data(
+            parse_raw(include_bytes!("../../data.json")).unwrap(),
+            &RoutingOptions::default(),
+        )
+        .unwrap();
+        let schedule = Schedule::new(&data, &HashMap::new()).unwrap();
+        // 충정로 and 을지로입구 lie on either side of 시청, where 2호선 runs start
+        let from = &data.stations["2호선_043"];
+        let to = &data.stations["2호선_002"];
+
+        let itinerary = schedule
+            .route(from, to, Time::parse("08:00").unwrap())
+            .unwrap();
+        assert_eq!(itinerary.legs.len(), 1);
+        let leg = &itinerary.legs[0];
+        assert_eq!(leg.line.name(), "2호선");
+        assert_eq!(
+            leg.stations.iter().map(Station::name).collect::<Vec<_>>(),
+            ["충정로(경기대입구)", "시청", "을지로입구"]
+        );
+    }
+}
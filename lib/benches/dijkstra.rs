@@ -0,0 +1,86 @@
+use std::{hint::black_box, time::Instant};
+
+use my_trash_bin_240704_lib::{
+    bidirectional::bidirectional_dijkstra,
+    dijkstra,
+    graph::{Graph, GraphBuilder, GraphEdge, NodeId},
+    NegativeDistance,
+};
+
+const SIDE: usize = 100;
+const QUERIES: usize = 200;
+
+type Search = for<'a> fn(
+    &'a Graph<usize, u32>,
+    NodeId,
+    NodeId,
+) -> Result<Option<Vec<GraphEdge<'a, u32>>>, NegativeDistance>;
+
+/// Deterministic pseudo-random numbers, so every run measures the same graph and queries
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1);
+        self.0 >> 33
+    }
+}
+
+/// SIDE × SIDE grid with random weights on the edges between neighbouring cells
+fn grid(random: &mut Lcg) -> Graph<usize, u32> {
+    let mut builder = GraphBuilder::new();
+    let ids = (0..SIDE * SIDE)
+        .map(|index| builder.add_node(index))
+        .collect::<Vec<_>>();
+    for row in 0..SIDE {
+        for column in 0..SIDE {
+            let index = row * SIDE + column;
+            if column + 1 < SIDE {
+                let weight = 1 + random.next() as u32 % 100;
+                builder.add_undirected_edge(ids[index], ids[index + 1], weight, ());
+            }
+            if row + 1 < SIDE {
+                let weight = 1 + random.next() as u32 % 100;
+                builder.add_undirected_edge(ids[index], ids[index + SIDE], weight, ());
+            }
+        }
+    }
+    builder.build()
+}
+
+fn main() {
+    let mut random = Lcg(42);
+    let started = Instant::now();
+    let graph = grid(&mut random);
+    println!("build {}x{} grid: {:?}", SIDE, SIDE, started.elapsed());
+
+    let queries = (0..QUERIES)
+        .map(|_| {
+            (
+                graph
+                    .node_id(random.next() as usize % graph.length())
+                    .unwrap(),
+                graph
+                    .node_id(random.next() as usize % graph.length())
+                    .unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    for (name, search) in [
+        ("dijkstra", dijkstra as Search),
+        ("bidirectional_dijkstra", bidirectional_dijkstra),
+    ] {
+        let started = Instant::now();
+        for &(from, to) in queries.iter() {
+            black_box(search(&graph, from, to).unwrap());
+        }
+        let elapsed = started.elapsed();
+        println!(
+            "{}: {} queries in {:?} ({:?} per query)",
+            name,
+            QUERIES,
+            elapsed,
+            elapsed / QUERIES as u32
+        );
+    }
+}
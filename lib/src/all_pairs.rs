@@ -0,0 +1,313 @@
+use std::ops::Sub;
+
+use crate::{
+    batch::parallel_map,
+    bellman_ford::{relax_all, NegativeCycle},
+    check_distances,
+    graph::{EdgeId, Graph, GraphDistance, GraphEdge, NodeId},
+    search, NegativeDistance,
+};
+
+/// Shortest distances between every pair of nodes, with the first edge of every shortest path
+#[derive(Debug, Clone)]
+pub struct AllPairs<D> {
+    length: usize,
+    /// row-major `length` × `length` matrix
+    distances: Vec<Option<D>>,
+    /// row-major `length` × `length` matrix of the first edge from row to column
+    next_hops: Vec<Option<EdgeId>>,
+}
+
+impl<D: GraphDistance> AllPairs<D> {
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Shortest distance from `from` to `to`, None if unreachable
+    pub fn distance(&self, from: NodeId, to: NodeId) -> Option<&D> {
+        self.distances[from.index() * self.length + to.index()].as_ref()
+    }
+
+    /// First edge of the shortest path from `from` to `to`, None if unreachable or equal
+    pub fn next_hop(&self, from: NodeId, to: NodeId) -> Option<EdgeId> {
+        self.next_hops[from.index() * self.length + to.index()]
+    }
+
+    /// Shortest path from `from` to `to`, following the next-hop matrix
+    pub fn path<'a, T, E>(
+        &self,
+        graph: &'a Graph<T, D, E>,
+        from: NodeId,
+        to: NodeId,
+    ) -> Option<Vec<GraphEdge<'a, D, E>>> {
+        self.distance(from, to)?;
+        let mut result = Vec::new();
+        let mut current = from;
+        while let Some(edge) = self.next_hop(current, to) {
+            let edge = graph.edge(edge);
+            current = edge.to;
+            result.push(edge);
+        }
+        Some(result)
+    }
+}
+
+/// Floyd–Warshall in O(V³) time and O(V²) memory, suited to small or dense graphs;
+/// handles negative distances but fails on a negative cycle, like [`johnson`]
+pub fn floyd_warshall<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+) -> Result<AllPairs<D>, NegativeCycle<'_, D, E>> {
+    let length = graph.length();
+    if graph.edges().any(|edge| *edge.distance < D::zero()) {
+        // as if from an extra node with an edge of distance zero to every node
+        relax_all(graph, vec![Some(D::zero()); length])?;
+    }
+
+    let mut distances = vec![None; length * length];
+    let mut next_hops = vec![None; length * length];
+    for node in graph.node_ids() {
+        distances[node.index() * length + node.index()] = Some(D::zero());
+    }
+    for edge in graph.edges() {
+        let index = edge.from.index() * length + edge.to.index();
+        if distances[index]
+            .as_ref()
+            .is_none_or(|distance| edge.distance < distance)
+        {
+            distances[index] = Some(edge.distance.clone());
+            next_hops[index] = Some(edge.id);
+        }
+    }
+
+    for k in 0..length {
+        for i in 0..length {
+            let Some(to_k) = distances[i * length + k].clone() else {
+                continue;
+            };
+            for j in 0..length {
+                let Some(from_k) = &distances[k * length + j] else {
+                    continue;
+                };
+                let through_k = to_k.clone() + from_k.clone();
+                if distances[i * length + j]
+                    .as_ref()
+                    .is_none_or(|distance| through_k < *distance)
+                {
+                    distances[i * length + j] = Some(through_k);
+                    next_hops[i * length + j] = next_hops[i * length + k];
+                }
+            }
+        }
+    }
+
+    Ok(AllPairs {
+        length,
+        distances,
+        next_hops,
+    })
+}
+
+/// Dijkstra from every node on up to `threads` threads, suited to large sparse graphs;
+/// fails on a negative distance, see [`johnson`]
+pub fn all_pairs_dijkstra<T: Sync, D: GraphDistance + Send + Sync, E: Sync>(
+    graph: &Graph<T, D, E>,
+    threads: usize,
+) -> Result<AllPairs<D>, NegativeDistance> {
+    check_distances(graph, |_| true)?;
+    Ok(every_tree(graph, threads))
+}
+
+/// [`all_pairs_dijkstra`] once distances are checked
+fn every_tree<T: Sync, D: GraphDistance + Send + Sync, E: Sync>(
+    graph: &Graph<T, D, E>,
+    threads: usize,
+) -> AllPairs<D> {
+    let length = graph.length();
+    // one row per index, empty for removed nodes
+    let sources = (0..length)
+        .map(|index| graph.node_id(index))
+        .collect::<Vec<_>>();
+    let rows = parallel_map(&sources, threads, |&from| {
+        let Some(from) = from else {
+            return (vec![None; length], vec![None; length]);
+        };
+        let tree = search(graph, from, None, |_| D::zero(), |_| true);
+        let mut next_hops = vec![None; length];
+        // predecessors are reached first, so their next hop is already known
+        for node in tree.reachable() {
+            if let Some(edge) = tree.predecessor(node) {
+                let edge = graph.edge(edge);
+                next_hops[node.index()] = if edge.from == from {
+                    Some(edge.id)
+                } else {
+                    next_hops[edge.from.index()]
+                };
+            }
+        }
+        let distances = (0..length)
+            .map(|index| {
+                let node = graph.node_id(index)?;
+                tree.distance(node).cloned()
+            })
+            .collect::<Vec<_>>();
+        (distances, next_hops)
+    });
+
+    let mut distances = Vec::with_capacity(length * length);
+    let mut next_hops = Vec::with_capacity(length * length);
+    for (row_distances, row_next_hops) in rows {
+        distances.extend(row_distances);
+        next_hops.extend(row_next_hops);
+    }
+    AllPairs {
+        length,
+        distances,
+        next_hops,
+    }
+}
+
+/// [`all_pairs_dijkstra`] allowing negative distances: Bellman-Ford finds a potential for
+/// every node that makes every edge non-negative once added; fails on a negative cycle
+pub fn johnson<T: Sync, D: GraphDistance + Sub<Output = D> + Send + Sync, E: Sync>(
+    graph: &Graph<T, D, E>,
+    threads: usize,
+) -> Result<AllPairs<D>, NegativeCycle<'_, D, E>> {
+    // as if from an extra node with an edge of distance zero to every node
+    let (potentials, _) = relax_all(graph, vec![Some(D::zero()); graph.length()])?;
+    let potentials = potentials
+        .into_iter()
+        .map(Option::unwrap)
+        .collect::<Vec<_>>();
+
+    // same node and edge ids as `graph`
+    let reweighted = graph.reweighted(|edge| {
+        let distance = edge.distance.clone() + potentials[edge.from.index()].clone()
+            - potentials[edge.to.index()].clone();
+        // clamped in case rounding made it slightly negative
+        distance.max(D::zero())
+    });
+    let mut result = every_tree(&reweighted, threads);
+
+    let length = result.length;
+    for (index, distance) in result.distances.iter_mut().enumerate() {
+        let (from, to) = (index / length, index % length);
+        *distance = distance
+            .take()
+            .map(|distance| distance - potentials[from].clone() + potentials[to].clone());
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{bellman_ford::bellman_ford, dijkstra, graph::GraphBuilder};
+
+    use super::*;
+
+    fn sample() -> Graph<usize, i32, ()> {
+        let mut builder = GraphBuilder::new();
+        let nodes = (0..6).map(|i| builder.add_node(i)).collect::<Vec<_>>();
+        for (from, to, distance) in [
+            (0, 1, 7),
+            (0, 2, 9),
+            (0, 5, 14),
+            (1, 2, 10),
+            (1, 3, 15),
+            (2, 3, 11),
+            (2, 5, 2),
+            (3, 4, 6),
+            (5, 4, 9),
+            (4, 0, 1),
+        ] {
+            builder.add_edge(nodes[from], nodes[to], distance, ());
+        }
+        builder.build()
+    }
+
+    fn cost(path: &[GraphEdge<'_, i32, ()>]) -> i32 {
+        path.iter().map(|edge| *edge.distance).sum()
+    }
+
+    #[test]
+    fn floyd_warshall_matches_dijkstra() {
+        let graph = sample();
+        let all_pairs = floyd_warshall(&graph).unwrap();
+        for from in graph.node_ids() {
+            for to in graph.node_ids() {
+                let expected = dijkstra(&graph, from, to).unwrap().map(|path| cost(&path));
+                assert_eq!(all_pairs.distance(from, to).copied(), expected);
+                let path = all_pairs.path(&graph, from, to);
+                assert_eq!(path.as_deref().map(cost), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn repeated_dijkstra_matches_floyd_warshall() {
+        let graph = sample();
+        let floyd_warshall = floyd_warshall(&graph).unwrap();
+        let dijkstra = all_pairs_dijkstra(&graph, 3).unwrap();
+        for from in graph.node_ids() {
+            for to in graph.node_ids() {
+                assert_eq!(
+                    dijkstra.distance(from, to),
+                    floyd_warshall.distance(from, to)
+                );
+                assert_eq!(
+                    dijkstra.path(&graph, from, to).as_deref().map(cost),
+                    floyd_warshall.distance(from, to).copied()
+                );
+            }
+        }
+        let node = graph.node_id(3).unwrap();
+        assert_eq!(dijkstra.next_hop(node, node), None);
+        assert_eq!(dijkstra.path(&graph, node, node), Some(vec![]));
+    }
+
+    #[test]
+    fn johnson_with_negative_edges() {
+        let graph = Graph::from_edges(
+            vec![0, 1, 2, 3],
+            vec![
+                (0, 1, 4i32, ()),
+                (0, 2, 5, ()),
+                (2, 1, -3, ()),
+                (1, 3, 2, ()),
+                (3, 0, -1, ()),
+            ],
+        )
+        .unwrap();
+        let all_pairs = johnson(&graph, 2).unwrap();
+        let floyd_warshall = floyd_warshall(&graph).unwrap();
+        for from in graph.node_ids() {
+            let tree = bellman_ford(&graph, from).unwrap();
+            for to in graph.node_ids() {
+                assert_eq!(all_pairs.distance(from, to), tree.distance(to));
+                assert_eq!(
+                    all_pairs.distance(from, to),
+                    floyd_warshall.distance(from, to)
+                );
+                assert_eq!(
+                    all_pairs.path(&graph, from, to).as_deref().map(cost),
+                    tree.distance(to).copied()
+                );
+            }
+        }
+
+        let graph = Graph::from_edges(vec![0, 1], vec![(0, 1, 1i32, ()), (1, 0, -2, ())]).unwrap();
+        assert_eq!(johnson(&graph, 2).unwrap_err().edges.len(), 2);
+    }
+
+    #[test]
+    fn floyd_warshall_refuses_negative_cycle() {
+        let graph = Graph::from_edges(vec![0, 1], vec![(0, 1, 1i32, ()), (1, 0, -2, ())]).unwrap();
+        let cycle = floyd_warshall(&graph).unwrap_err();
+        let mut distances = cycle
+            .edges
+            .iter()
+            .map(|edge| *edge.distance)
+            .collect::<Vec<_>>();
+        distances.sort();
+        assert_eq!(distances, vec![-2, 1]);
+    }
+}
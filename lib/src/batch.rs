@@ -0,0 +1,104 @@
+use std::{
+    num::NonZeroUsize,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use crate::{
+    check_distances,
+    graph::{Graph, GraphDistance, NodeId},
+    search, NegativeDistance, Path,
+};
+
+/// Number of threads the machine can run in parallel, at least 1
+pub fn available_threads() -> usize {
+    thread::available_parallelism()
+        .map(NonZeroUsize::get)
+        .unwrap_or(1)
+}
+
+/// Applies `f` to every item on up to `threads` scoped threads; results keep the order of `items`
+pub fn parallel_map<I: Sync, R: Send>(
+    items: &[I],
+    threads: usize,
+    f: impl Fn(&I) -> R + Sync,
+) -> Vec<R> {
+    let threads = threads.clamp(1, items.len().max(1));
+    let next = AtomicUsize::new(0);
+    let mut results = (0..items.len()).map(|_| None).collect::<Vec<_>>();
+
+    thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        if index >= items.len() {
+                            break done;
+                        }
+                        done.push((index, f(&items[index])));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            for (index, result) in worker.join().unwrap() {
+                results[index] = Some(result);
+            }
+        }
+    });
+
+    results.into_iter().map(Option::unwrap).collect()
+}
+
+/// [`dijkstra`](crate::dijkstra) for every `(from, to)` query, answered on up to `threads` threads;
+/// fails on a negative distance
+pub fn dijkstra_batch<'a, T: Sync, D: GraphDistance + Sync, E: Sync>(
+    graph: &'a Graph<T, D, E>,
+    queries: &[(NodeId, NodeId)],
+    threads: usize,
+) -> Result<Vec<Option<Path<'a, D, E>>>, NegativeDistance> {
+    check_distances(graph, |_| true)?;
+    Ok(parallel_map(queries, threads, |&(from, to)| {
+        search(graph, from, Some(to), |_| D::zero(), |_| true).path_to(graph, to)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dijkstra, graph::GraphBuilder};
+
+    use super::*;
+
+    #[test]
+    fn same_as_sequential() {
+        let mut builder = GraphBuilder::new();
+        let nodes = (0..20).map(|i| builder.add_node(i)).collect::<Vec<_>>();
+        for i in 0..20 {
+            builder.add_edge(nodes[i], nodes[(i + 1) % 20], 1u32, ());
+            builder.add_edge(nodes[i], nodes[(i + 7) % 20], 5u32, ());
+        }
+        let graph = builder.build();
+        let queries = nodes
+            .iter()
+            .flat_map(|&from| nodes.iter().map(move |&to| (from, to)))
+            .collect::<Vec<_>>();
+
+        let parallel = dijkstra_batch(&graph, &queries, 4).unwrap();
+        assert_eq!(parallel.len(), queries.len());
+        for (&(from, to), result) in queries.iter().zip(parallel) {
+            assert_eq!(Ok(result), dijkstra(&graph, from, to));
+        }
+    }
+
+    #[test]
+    fn parallel_map_keeps_order() {
+        let items = (0..1000).collect::<Vec<_>>();
+        assert_eq!(
+            parallel_map(&items, 8, |i| i * 2),
+            (0..2000).step_by(2).collect::<Vec<_>>()
+        );
+        assert_eq!(parallel_map(&[] as &[u32], 8, |i| *i), vec![]);
+    }
+}
@@ -0,0 +1,181 @@
+use std::{error::Error, fmt};
+
+use crate::{
+    graph::{EdgeId, Graph, GraphDistance, GraphEdge, NodeId},
+    search, Path, ShortestPathTree,
+};
+
+/// Cycle whose edges add up to a negative distance, so that no shortest path exists through it
+#[derive(Debug, Clone, PartialEq)]
+pub struct NegativeCycle<'a, D, E = ()> {
+    /// edges of the cycle in order, the last one leading back to where the first one starts
+    pub edges: Vec<GraphEdge<'a, D, E>>,
+}
+
+impl<D, E> fmt::Display for NegativeCycle<'_, D, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Negative cycle of {} edges", self.edges.len())
+    }
+}
+
+impl<D: fmt::Debug, E: fmt::Debug> Error for NegativeCycle<'_, D, E> {}
+
+/// Distance to every node and the edge it was last improved through
+type Relaxed<D> = (Vec<Option<D>>, Vec<Option<EdgeId>>);
+
+/// Relaxes every edge until nothing changes, starting from `distances`;
+/// returns the distances and the edge each node was last improved through
+pub(crate) fn relax_all<'a, T, D: GraphDistance, E>(
+    graph: &'a Graph<T, D, E>,
+    mut distances: Vec<Option<D>>,
+) -> Result<Relaxed<D>, NegativeCycle<'a, D, E>> {
+    let mut predecessors = vec![None; graph.length()];
+    let mut last_changed = None;
+    for _ in 0..graph.length() {
+        last_changed = None;
+        for edge in graph.edges() {
+            let Some(from) = &distances[edge.from.index()] else {
+                continue;
+            };
+            let distance = from.clone() + edge.distance.clone();
+            if distances[edge.to.index()]
+                .as_ref()
+                .is_none_or(|to| distance < *to)
+            {
+                distances[edge.to.index()] = Some(distance);
+                predecessors[edge.to.index()] = Some(edge.id);
+                last_changed = Some(edge.to);
+            }
+        }
+        if last_changed.is_none() {
+            return Ok((distances, predecessors));
+        }
+    }
+
+    // still improving after `length` rounds, which only a negative cycle allows;
+    // walking back `length` predecessors from a node improved in the last round ends inside it
+    let mut node = last_changed.unwrap();
+    for _ in 0..graph.length() {
+        node = graph.edge(predecessors[node.index()].unwrap()).from;
+    }
+    let start = node;
+    let mut edges = Vec::new();
+    loop {
+        let edge = graph.edge(predecessors[node.index()].unwrap());
+        edges.push(edge);
+        node = edge.from;
+        if node == start {
+            break;
+        }
+    }
+    edges.reverse();
+    Err(NegativeCycle { edges })
+}
+
+/// Shortest paths from `from` to every reachable node, allowing negative distances;
+/// fails with a negative cycle reachable from `from` if there is one
+pub fn bellman_ford<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    from: NodeId,
+) -> Result<ShortestPathTree<D>, NegativeCycle<'_, D, E>> {
+    let mut distances = vec![None; graph.length()];
+    distances[from.index()] = Some(D::zero());
+    let (distances, mut predecessors) = relax_all(graph, distances)?;
+    predecessors[from.index()] = None;
+
+    let mut order = graph
+        .node_ids()
+        .filter(|node| distances[node.index()].is_some())
+        .collect::<Vec<_>>();
+    order.sort_by(|a, b| distances[a.index()].cmp(&distances[b.index()]));
+    Ok(ShortestPathTree {
+        from,
+        distances,
+        predecessors,
+        order,
+    })
+}
+
+/// Shortest path from `from` to `to` with [`dijkstra`](crate::dijkstra), or with [`bellman_ford`] if the graph
+/// has a negative distance; fails if a negative cycle is reachable from `from`
+pub fn shortest_path<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    from: NodeId,
+    to: NodeId,
+) -> Result<Option<Path<'_, D, E>>, NegativeCycle<'_, D, E>> {
+    if graph.edges().any(|edge| *edge.distance < D::zero()) {
+        Ok(bellman_ford(graph, from)?.path_to(graph, to))
+    } else {
+        Ok(search(graph, from, Some(to), |_| D::zero(), |_| true).path_to(graph, to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dijkstra, graph::GraphBuilder, NegativeDistance};
+
+    use super::*;
+
+    #[test]
+    fn negative_edges() {
+        let mut builder = GraphBuilder::new();
+        let nodes = (0..4).map(|i| builder.add_node(i)).collect::<Vec<_>>();
+        builder.add_edge(nodes[0], nodes[1], 4i32, ());
+        builder.add_edge(nodes[0], nodes[2], 5, ());
+        builder.add_edge(nodes[2], nodes[1], -3, ());
+        builder.add_edge(nodes[1], nodes[3], 2, ());
+        let graph = builder.build();
+
+        let tree = bellman_ford(&graph, nodes[0]).unwrap();
+        assert_eq!(
+            nodes
+                .iter()
+                .map(|&node| tree.distance(node).copied())
+                .collect::<Vec<_>>(),
+            vec![Some(0), Some(2), Some(5), Some(4)]
+        );
+        let path = shortest_path(&graph, nodes[0], nodes[3]).unwrap().unwrap();
+        assert_eq!(
+            path.iter().map(|edge| edge.to).collect::<Vec<_>>(),
+            vec![nodes[2], nodes[1], nodes[3]]
+        );
+        assert_eq!(shortest_path(&graph, nodes[3], nodes[0]), Ok(None));
+    }
+
+    #[test]
+    fn finds_negative_cycle() {
+        let mut builder = GraphBuilder::new();
+        let nodes = (0..5).map(|i| builder.add_node(i)).collect::<Vec<_>>();
+        builder.add_edge(nodes[0], nodes[1], 1i32, ());
+        builder.add_edge(nodes[1], nodes[2], 1, ());
+        builder.add_edge(nodes[2], nodes[3], -4, ());
+        builder.add_edge(nodes[3], nodes[1], 2, ());
+        builder.add_edge(nodes[3], nodes[4], 1, ());
+        let graph = builder.build();
+
+        let cycle = bellman_ford(&graph, nodes[0]).unwrap_err();
+        assert_eq!(cycle.edges.len(), 3);
+        assert_eq!(
+            cycle.edges.iter().map(|edge| *edge.distance).sum::<i32>(),
+            -1
+        );
+        for (edge, next) in cycle.edges.iter().zip(cycle.edges.iter().cycle().skip(1)) {
+            assert_eq!(edge.to, next.from);
+        }
+        assert!(shortest_path(&graph, nodes[0], nodes[4]).is_err());
+        // unreachable from the cycle
+        assert!(bellman_ford(&graph, nodes[4]).is_ok());
+    }
+
+    #[test]
+    fn dijkstra_refuses_negative_edges() {
+        let graph = Graph::from_edges(vec![0, 1], vec![(0, 1, -1i32, ())]).unwrap();
+        let (from, to) = (graph.node_id(0).unwrap(), graph.node_id(1).unwrap());
+        let edge = graph.adjacent(from).next().unwrap().id;
+        assert_eq!(dijkstra(&graph, from, to), Err(NegativeDistance(edge)));
+        assert_eq!(
+            shortest_path(&graph, from, to).unwrap().unwrap()[0].id,
+            edge
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use std::{error::Error, fmt};
+
+/// Departure of a vehicle from one stop and its arrival at the next one, times in seconds
+#[derive(Clone, Debug, PartialEq)]
+pub struct Connection<P = ()> {
+    pub from: usize,
+    pub to: usize,
+    pub departure: u32,
+    pub arrival: u32,
+    /// vehicle run the connection belongs to; staying on board needs no transfer time
+    pub trip: usize,
+    pub payload: P,
+}
+
+/// Ride on one trip, as indices of its first and last connections in [`Timetable::connections`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JourneyLeg {
+    pub first: usize,
+    pub last: usize,
+}
+
+/// Connection arriving before it departs or referring to a stop out of range, by its index in
+/// the connections given to [`Timetable::new`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidConnection(pub usize);
+
+impl fmt::Display for InvalidConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Connection {} arrives before it departs or refers to an unknown stop",
+            self.0
+        )
+    }
+}
+
+impl Error for InvalidConnection {}
+
+/// Every connection of a network sorted by departure, for earliest-arrival queries with the
+/// Connection Scan Algorithm
+pub struct Timetable<P = ()> {
+    connections: Vec<Connection<P>>,
+    /// seconds needed to change trips at every stop
+    transfer_times: Vec<u32>,
+}
+
+impl<P> Timetable<P> {
+    /// Timetable of stops `0..transfer_times.len()`; fails on the first connection that arrives
+    /// before it departs or refers to a stop out of range
+    pub fn new(
+        mut connections: Vec<Connection<P>>,
+        transfer_times: Vec<u32>,
+    ) -> Result<Timetable<P>, InvalidConnection> {
+        if let Some(index) = connections.iter().position(|connection| {
+            connection.arrival < connection.departure
+                || connection.from >= transfer_times.len()
+                || connection.to >= transfer_times.len()
+        }) {
+            return Err(InvalidConnection(index));
+        }
+        connections.sort_by_key(|connection| (connection.departure, connection.arrival));
+        Ok(Timetable {
+            connections,
+            transfer_times,
+        })
+    }
+
+    pub fn connections(&self) -> &[Connection<P>] {
+        &self.connections
+    }
+
+    /// Legs of a journey leaving `from` no earlier than `departure` and reaching `to` as early as
+    /// possible; None if `to` cannot be reached, an empty journey if `from == to`
+    pub fn earliest_arrival(
+        &self,
+        from: usize,
+        to: usize,
+        departure: u32,
+    ) -> Option<Vec<JourneyLeg>> {
+        let stops = self.transfer_times.len();
+        let mut arrivals = vec![u32::MAX; stops];
+        // earliest time a trip can be boarded at every stop
+        let mut ready = vec![u32::MAX; stops];
+        // where every trip was boarded, if it was
+        let mut boarded = Vec::<Option<usize>>::new();
+        // last leg reaching every stop
+        let mut reached_by = vec![None::<JourneyLeg>; stops];
+        arrivals[from] = departure;
+        ready[from] = departure;
+
+        let start = self
+            .connections
+            .partition_point(|connection| connection.departure < departure);
+        for (index, connection) in self.connections.iter().enumerate().skip(start) {
+            if arrivals[to] <= connection.departure {
+                break;
+            }
+            if boarded.len() <= connection.trip {
+                boarded.resize(connection.trip + 1, None);
+            }
+            if boarded[connection.trip].is_none() && ready[connection.from] <= connection.departure
+            {
+                boarded[connection.trip] = Some(index);
+            }
+            let Some(first) = boarded[connection.trip] else {
+                continue;
+            };
+            if connection.arrival < arrivals[connection.to] {
+                arrivals[connection.to] = connection.arrival;
+                ready[connection.to] = connection
+                    .arrival
+                    .saturating_add(self.transfer_times[connection.to]);
+                reached_by[connection.to] = Some(JourneyLeg { first, last: index });
+            }
+        }
+
+        if arrivals[to] == u32::MAX {
+            return None;
+        }
+        let mut legs = Vec::new();
+        let mut stop = to;
+        while stop != from {
+            let leg = reached_by[stop].unwrap();
+            stop = self.connections[leg.first].from;
+            legs.push(leg);
+        }
+        legs.reverse();
+        Some(legs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection(from: usize, to: usize, departure: u32, arrival: u32, trip: usize) -> Connection {
+        Connection {
+            from,
+            to,
+            departure,
+            arrival,
+            trip,
+            payload: (),
+        }
+    }
+
+    #[test]
+    fn earliest_arrival() {
+        // trip 0 runs 0 -> 1 -> 2 slowly, trip 1 runs 1 -> 2 quickly, trip 2 leaves 1 too soon
+        // to catch after arriving on trip 0, trip 3 runs 2 -> 3
+        let timetable = Timetable::new(
+            vec![
+                connection(0, 1, 100, 200, 0),
+                connection(1, 2, 210, 500, 0),
+                connection(1, 2, 205, 300, 2),
+                connection(1, 2, 260, 350, 1),
+                connection(2, 3, 400, 450, 3),
+                connection(2, 3, 320, 340, 4),
+            ],
+            vec![0, 60, 30, 0],
+        )
+        .unwrap();
+        let legs = |from, to, departure| {
+            timetable.earliest_arrival(from, to, departure).map(|legs| {
+                legs.iter()
+                    .map(|leg| {
+                        let (first, last) = (
+                            &timetable.connections()[leg.first],
+                            &timetable.connections()[leg.last],
+                        );
+                        (
+                            first.trip,
+                            first.from,
+                            last.to,
+                            first.departure,
+                            last.arrival,
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(
+            legs(0, 3, 0),
+            Some(vec![
+                (0, 0, 1, 100, 200),
+                (1, 1, 2, 260, 350),
+                (3, 2, 3, 400, 450)
+            ])
+        );
+        assert_eq!(legs(1, 2, 0), Some(vec![(2, 1, 2, 205, 300)]));
+        assert_eq!(legs(0, 3, 101), None);
+        assert_eq!(legs(3, 0, 0), None);
+        assert_eq!(legs(2, 2, 0), Some(vec![]));
+    }
+
+    #[test]
+    fn invalid_connections() {
+        let timetable = |connections| Timetable::new(connections, vec![0, 0]).err();
+        assert_eq!(timetable(vec![connection(0, 1, 100, 100, 0)]), None);
+        assert_eq!(
+            timetable(vec![
+                connection(0, 1, 100, 200, 0),
+                connection(1, 0, 300, 250, 1)
+            ]),
+            Some(InvalidConnection(1))
+        );
+        assert_eq!(
+            timetable(vec![connection(0, 2, 100, 200, 0)]),
+            Some(InvalidConnection(0))
+        );
+        assert_eq!(
+            timetable(vec![connection(2, 1, 100, 200, 0)]),
+            Some(InvalidConnection(0))
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt::Write;
+
+use crate::graph::{Graph, GraphDistance, GraphEdge, NodeId};
+
+/// Escapes `value` for a double-quoted DOT string
+fn dot_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escapes `value` for XML text and attribute values
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// GraphViz DOT digraph of `graph`, labelling nodes and edges with the given formatters
+pub fn to_dot<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    node_label: impl Fn(NodeId, &T) -> String,
+    edge_label: impl Fn(&GraphEdge<'_, D, E>) -> String,
+) -> String {
+    let mut result = String::from("digraph {\n");
+    for node in graph.node_ids() {
+        let label = dot_escape(&node_label(node, &graph[node]));
+        writeln!(result, "  n{} [label=\"{}\"];", node.index(), label).unwrap();
+    }
+    for edge in graph.edges() {
+        writeln!(
+            result,
+            "  n{} -> n{} [label=\"{}\"];",
+            edge.from.index(),
+            edge.to.index(),
+            dot_escape(&edge_label(&edge))
+        )
+        .unwrap();
+    }
+    result.push_str("}\n");
+    result
+}
+
+/// GraphML document of `graph`, with a `label` attribute on nodes and edges from the given
+/// formatters
+pub fn to_graphml<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    node_label: impl Fn(NodeId, &T) -> String,
+    edge_label: impl Fn(&GraphEdge<'_, D, E>) -> String,
+) -> String {
+    let mut result = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        "  <key id=\"node_label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n",
+        "  <key id=\"edge_label\" for=\"edge\" attr.name=\"label\" attr.type=\"string\"/>\n",
+        "  <graph edgedefault=\"directed\">\n",
+    ));
+    for node in graph.node_ids() {
+        writeln!(
+            result,
+            "    <node id=\"n{}\"><data key=\"node_label\">{}</data></node>",
+            node.index(),
+            xml_escape(&node_label(node, &graph[node]))
+        )
+        .unwrap();
+    }
+    for edge in graph.edges() {
+        writeln!(
+            result,
+            "    <edge id=\"e{}\" source=\"n{}\" target=\"n{}\"><data key=\"edge_label\">{}</data></edge>",
+            edge.id.index(),
+            edge.from.index(),
+            edge.to.index(),
+            xml_escape(&edge_label(&edge))
+        )
+        .unwrap();
+    }
+    result.push_str("  </graph>\n</graphml>\n");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_small_graph() {
+        let graph = Graph::from_edges(
+            vec!["a \"quoted\"", "b & <c>"],
+            vec![(0, 1, 2u32, "x"), (1, 0, 3u32, "y")],
+        )
+        .unwrap();
+        let node_label = |_, value: &&str| value.to_string();
+        let edge_label =
+            |edge: &GraphEdge<'_, u32, &str>| format!("{} {}", edge.payload, edge.distance);
+
+        assert_eq!(
+            to_dot(&graph, node_label, edge_label),
+            concat!(
+                "digraph {\n",
+                "  n0 [label=\"a \\\"quoted\\\"\"];\n",
+                "  n1 [label=\"b & <c>\"];\n",
+                "  n0 -> n1 [label=\"x 2\"];\n",
+                "  n1 -> n0 [label=\"y 3\"];\n",
+                "}\n"
+            )
+        );
+
+        let graphml = to_graphml(&graph, node_label, edge_label);
+        assert!(graphml
+            .contains("<node id=\"n1\"><data key=\"node_label\">b &amp; &lt;c&gt;</data></node>"));
+        assert!(graphml.contains(
+            "<edge id=\"e0\" source=\"n0\" target=\"n1\"><data key=\"edge_label\">x 2</data></edge>"
+        ));
+        assert!(graphml.ends_with("</graphml>\n"));
+    }
+}
@@ -1,10 +1,7 @@
 use std::{
-    cell::RefCell,
     error::Error,
-    hash::{Hash, Hasher},
-    io,
-    ops::{Add, Deref, Index},
-    rc::{Rc, Weak},
+    fmt,
+    ops::{Add, Deref, Index, Sub},
 };
 
 pub trait GraphDistance: Ord + Add<Output = Self> + Clone {
@@ -37,9 +34,9 @@ impl_graph_distance!(isize);
 impl_graph_distance!(GraphDistanceF32, GraphDistanceF32(0f32));
 impl_graph_distance!(GraphDistanceF64, GraphDistanceF64(0f64));
 
-#[derive(PartialEq, PartialOrd, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug)]
 pub struct GraphDistanceF32(f32);
-#[derive(PartialEq, PartialOrd, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug)]
 pub struct GraphDistanceF64(f64);
 
 impl Deref for GraphDistanceF32 {
@@ -52,9 +49,15 @@ impl Deref for GraphDistanceF32 {
 
 impl Eq for GraphDistanceF32 {}
 
+impl PartialOrd for GraphDistanceF32 {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Ord for GraphDistanceF32 {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+        self.0.partial_cmp(&other.0).unwrap()
     }
 }
 
@@ -67,6 +70,14 @@ impl Add for GraphDistanceF32 {
     }
 }
 
+impl Sub for GraphDistanceF32 {
+    type Output = GraphDistanceF32;
+
+    fn sub(self, rhs: Self) -> GraphDistanceF32 {
+        GraphDistanceF32::new(self.0 - rhs.0)
+    }
+}
+
 impl GraphDistanceF32 {
     pub fn new(f: f32) -> GraphDistanceF32 {
         if f.is_nan() {
@@ -86,9 +97,15 @@ impl Deref for GraphDistanceF64 {
 
 impl Eq for GraphDistanceF64 {}
 
+impl PartialOrd for GraphDistanceF64 {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Ord for GraphDistanceF64 {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+        self.0.partial_cmp(&other.0).unwrap()
     }
 }
 
@@ -101,6 +118,14 @@ impl Add for GraphDistanceF64 {
     }
 }
 
+impl Sub for GraphDistanceF64 {
+    type Output = GraphDistanceF64;
+
+    fn sub(self, rhs: Self) -> GraphDistanceF64 {
+        GraphDistanceF64::new(self.0 - rhs.0)
+    }
+}
+
 impl GraphDistanceF64 {
     pub fn new(f: f64) -> GraphDistanceF64 {
         if f.is_nan() {
@@ -110,231 +135,484 @@ impl GraphDistanceF64 {
     }
 }
 
-pub struct Graph<T, D: GraphDistance> {
-    nodes: Vec<GraphNode<T, D>>,
+/// Handle of a node of a [`Graph`] or [`GraphBuilder`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
 }
 
-#[derive(Debug)]
-struct GraphNodeInternal<T, D: GraphDistance> {
-    adjacent_nodes: Vec<GraphEdgeInternal<T, D>>,
-    reverse_adjacent_nodes: Vec<GraphEdgeInternal<T, D>>,
-    data: T,
+/// Handle of an edge of a [`Graph`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EdgeId(u32);
+
+impl EdgeId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
 }
 
 #[derive(Debug)]
-pub struct GraphNode<T, D: GraphDistance> {
-    internal: Rc<RefCell<GraphNodeInternal<T, D>>>,
+struct GraphNodeEntry<T> {
+    data: T,
+    adjacent_edges: Vec<EdgeId>,
+    reverse_adjacent_edges: Vec<EdgeId>,
 }
 
-impl<T: Clone, D: GraphDistance> GraphNode<T, D> {
-    pub fn value(&self) -> T {
-        return self.internal.borrow().data.clone();
-    }
+#[derive(Debug)]
+struct GraphEdgeEntry<D, E> {
+    from: NodeId,
+    to: NodeId,
+    distance: D,
+    payload: E,
 }
 
-impl<T, D: GraphDistance> Clone for GraphNode<T, D> {
-    fn clone(&self) -> Self {
-        Self {
-            internal: self.internal.clone(),
-        }
-    }
+/// Directed graph stored as arenas of nodes and edges, addressed by [`NodeId`] and [`EdgeId`];
+/// removed nodes and edges leave an empty slot behind, so ids are never reused
+#[derive(Debug)]
+pub struct Graph<T, D: GraphDistance, E = ()> {
+    nodes: Vec<Option<GraphNodeEntry<T>>>,
+    edges: Vec<Option<GraphEdgeEntry<D, E>>>,
+    /// Live edges with a negative distance, so that searches check them without a scan
+    negative_edges: usize,
 }
 
+/// Borrowed view of an edge of a [`Graph`]
 #[derive(Debug)]
-struct GraphEdgeInternal<T, D: GraphDistance> {
-    pub from: Weak<RefCell<GraphNodeInternal<T, D>>>,
-    pub to: Weak<RefCell<GraphNodeInternal<T, D>>>,
-    pub distance: D,
+pub struct GraphEdge<'a, D, E = ()> {
+    pub id: EdgeId,
+    pub from: NodeId,
+    pub to: NodeId,
+    pub distance: &'a D,
+    pub payload: &'a E,
 }
 
-impl<T, D: GraphDistance> Clone for GraphEdgeInternal<T, D> {
+impl<D, E> Clone for GraphEdge<'_, D, E> {
     fn clone(&self) -> Self {
-        Self {
-            from: self.from.clone(),
-            to: self.to.clone(),
-            distance: self.distance.clone(),
-        }
+        *self
     }
 }
 
-impl<T, D: GraphDistance> GraphEdgeInternal<T, D> {
-    fn to_graph_edge(&self) -> GraphEdge<T, D> {
-        GraphEdge {
-            from: GraphNode {
-                internal: self.from.upgrade().unwrap(),
-            },
-            to: GraphNode {
-                internal: self.to.upgrade().unwrap(),
-            },
-            distance: self.distance.clone(),
-        }
-    }
-}
-
-#[derive(Debug)]
-pub struct GraphEdge<T, D: GraphDistance> {
-    pub from: GraphNode<T, D>,
-    pub to: GraphNode<T, D>,
-    pub distance: D,
-}
+impl<D, E> Copy for GraphEdge<'_, D, E> {}
 
-impl<T, D: GraphDistance> PartialEq for GraphEdge<T, D> {
+impl<D: PartialEq, E: PartialEq> PartialEq for GraphEdge<'_, D, E> {
     fn eq(&self, other: &Self) -> bool {
-        self.from.eq(&other.from) && self.to.eq(&other.to) && self.distance.eq(&other.distance)
+        self.id == other.id
+            && self.from == other.from
+            && self.to == other.to
+            && self.distance == other.distance
+            && self.payload == other.payload
     }
 }
 
-impl<T, D: GraphDistance> Clone for GraphEdge<T, D> {
-    fn clone(&self) -> Self {
-        Self {
-            from: self.from.clone(),
-            to: self.to.clone(),
-            distance: self.distance.clone(),
+/// Reason a graph could not be built from its input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    /// The adjacency matrix has `len` rows, or its row `row` has `len` entries, instead of one
+    /// per node
+    MatrixSizeMismatch {
+        expected: usize,
+        row: Option<usize>,
+        len: usize,
+    },
+    /// The adjacency matrix connects `node` to itself
+    DiagonalEdge { node: usize },
+    /// The `edge`th edge refers to `node`, but there are only `length` nodes
+    NodeOutOfRange {
+        edge: usize,
+        node: usize,
+        length: usize,
+    },
+    /// The node was removed or belongs to another graph
+    MissingNode(NodeId),
+    /// The edge was removed or belongs to another graph
+    MissingEdge(EdgeId),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::MatrixSizeMismatch {
+                expected,
+                row: None,
+                len,
+            } => write!(
+                f,
+                "Adjacent matrix size mismatch: {} rows for {} nodes",
+                len, expected
+            ),
+            GraphError::MatrixSizeMismatch {
+                expected,
+                row: Some(row),
+                len,
+            } => write!(
+                f,
+                "Adjacent matrix size mismatch: row {} has {} entries for {} nodes",
+                row, len, expected
+            ),
+            GraphError::DiagonalEdge { node } => write!(
+                f,
+                "Adjacent matrix should have None on its diagonal, but connects {} to itself",
+                node
+            ),
+            GraphError::NodeOutOfRange { edge, node, length } => write!(
+                f,
+                "Edge {} refers to node {}, out of range for {} nodes",
+                edge, node, length
+            ),
+            GraphError::MissingNode(node) => write!(f, "No node {} in the graph", node.index()),
+            GraphError::MissingEdge(edge) => write!(f, "No edge {} in the graph", edge.index()),
         }
     }
 }
 
+impl Error for GraphError {}
+
 impl<T, D: GraphDistance> Graph<T, D> {
     pub fn new(
         values: Vec<T>,
         adjacent_matrix: Vec<Vec<Option<D>>>,
-    ) -> Result<Graph<T, D>, Box<dyn Error>> {
+    ) -> Result<Graph<T, D>, GraphError> {
         let length = values.len();
-        let nodes: Vec<GraphNode<T, D>> = values
-            .into_iter()
-            .map(|x| GraphNode {
-                internal: Rc::new(RefCell::new(GraphNodeInternal {
-                    adjacent_nodes: vec![],
-                    reverse_adjacent_nodes: vec![],
-                    data: x,
-                })),
-            })
-            .collect();
-
-        if adjacent_matrix.len() != length || adjacent_matrix.iter().any(|x| x.len() != length) {
-            return Err(Box::new(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "Adjacent matrix size mismatch",
-            )));
+        if adjacent_matrix.len() != length {
+            return Err(GraphError::MatrixSizeMismatch {
+                expected: length,
+                row: None,
+                len: adjacent_matrix.len(),
+            });
         }
-        if adjacent_matrix
+        if let Some((row, x)) = adjacent_matrix
             .iter()
             .enumerate()
-            .any(|(i, l)| l[i].is_some())
+            .find(|(_, x)| x.len() != length)
         {
-            return Err(Box::new(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "Adjacent matrix should have None on its diagonal",
-            )));
+            return Err(GraphError::MatrixSizeMismatch {
+                expected: length,
+                row: Some(row),
+                len: x.len(),
+            });
+        }
+        if let Some(node) = (0..length).find(|&i| adjacent_matrix[i][i].is_some()) {
+            return Err(GraphError::DiagonalEdge { node });
         }
 
-        for i in 0..length {
-            for j in 0..length {
-                if i == j {
-                    continue;
-                }
-                if let Some(distance) = adjacent_matrix[i][j].clone() {
-                    let from = Rc::downgrade(&Rc::clone(&nodes[i].internal));
-                    let to = Rc::downgrade(&Rc::clone(&nodes[j].internal));
-                    let internal = GraphEdgeInternal { from, to, distance };
-                    nodes[i]
-                        .internal
-                        .borrow_mut()
-                        .adjacent_nodes
-                        .push(internal.clone());
-                    nodes[j]
-                        .internal
-                        .borrow_mut()
-                        .reverse_adjacent_nodes
-                        .push(internal);
-                }
+        let edges = adjacent_matrix
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, row)| {
+                row.into_iter()
+                    .enumerate()
+                    .filter_map(move |(j, distance)| distance.map(|distance| (i, j, distance, ())))
+            })
+            .collect();
+        Graph::from_edges(values, edges)
+    }
+}
+
+impl<T, D: GraphDistance, E> Graph<T, D, E> {
+    /// Builds a graph from `(from, to, distance, payload)` edges between indices of `values`;
+    /// the same pair of nodes may be connected by any number of edges
+    pub fn from_edges(
+        values: Vec<T>,
+        edges: Vec<(usize, usize, D, E)>,
+    ) -> Result<Graph<T, D, E>, GraphError> {
+        let length = values.len();
+        for (edge, (i, j, _, _)) in edges.iter().enumerate() {
+            if let Some(&node) = [i, j].into_iter().find(|node| **node >= length) {
+                return Err(GraphError::NodeOutOfRange { edge, node, length });
             }
         }
 
-        Ok(Graph { nodes })
+        let mut builder = GraphBuilder::new();
+        let ids = values
+            .into_iter()
+            .map(|value| builder.add_node(value))
+            .collect::<Vec<_>>();
+        for (i, j, distance, payload) in edges {
+            builder.add_edge(ids[i], ids[j], distance, payload);
+        }
+        Ok(builder.build())
     }
 
+    /// Bound of every node index, removed nodes included, to size per-node arrays
     pub fn length(&self) -> usize {
         self.nodes.len()
     }
-}
 
-impl<T, D: GraphDistance> Index<usize> for Graph<T, D> {
-    type Output = GraphNode<T, D>;
+    /// Number of nodes, not counting removed ones
+    pub fn node_count(&self) -> usize {
+        self.nodes.iter().flatten().count()
+    }
 
-    fn index(&self, index: usize) -> &GraphNode<T, D> {
-        return &self.nodes[index];
+    /// Number of edges, not counting removed ones
+    pub fn edge_count(&self) -> usize {
+        self.edges.iter().flatten().count()
     }
-}
 
-impl<T, D: GraphDistance> Deref for GraphNodeInternal<T, D> {
-    type Target = T;
+    /// Id of the `index`th node, in the order the nodes were added, unless it was removed
+    pub fn node_id(&self, index: usize) -> Option<NodeId> {
+        let id = NodeId(index.try_into().ok()?);
+        self.contains_node(id).then_some(id)
+    }
 
-    fn deref(&self) -> &T {
-        &self.data
+    pub fn node_ids(&self) -> impl Iterator<Item = NodeId> + '_ {
+        (0..self.nodes.len() as u32)
+            .map(NodeId)
+            .filter(|&id| self.contains_node(id))
     }
-}
 
-pub struct GraphNodeAdjacent<T, D: GraphDistance> {
-    pub nodes: Vec<GraphEdge<T, D>>,
-}
+    pub fn contains_node(&self, id: NodeId) -> bool {
+        matches!(self.nodes.get(id.index()), Some(Some(_)))
+    }
 
-impl<T, D: GraphDistance> Clone for GraphNodeAdjacent<T, D> {
-    fn clone(&self) -> Self {
-        Self {
-            nodes: self.nodes.clone(),
+    pub fn contains_edge(&self, id: EdgeId) -> bool {
+        matches!(self.edges.get(id.index()), Some(Some(_)))
+    }
+
+    /// Panics if `id` was removed; edges reached through [`Graph::adjacent`],
+    /// [`Graph::reverse_adjacent`] or [`Graph::edges`] never are
+    pub fn edge(&self, id: EdgeId) -> GraphEdge<'_, D, E> {
+        self.get_edge(id).expect("Graph::edge: removed edge")
+    }
+
+    pub fn get_edge(&self, id: EdgeId) -> Option<GraphEdge<'_, D, E>> {
+        let entry = self.edges.get(id.index())?.as_ref()?;
+        Some(GraphEdge {
+            id,
+            from: entry.from,
+            to: entry.to,
+            distance: &entry.distance,
+            payload: &entry.payload,
+        })
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = GraphEdge<'_, D, E>> {
+        (0..self.edges.len() as u32).filter_map(|id| self.get_edge(EdgeId(id)))
+    }
+
+    /// Edges leaving `node`, none if it was removed
+    pub fn adjacent(&self, node: NodeId) -> impl Iterator<Item = GraphEdge<'_, D, E>> {
+        self.nodes
+            .get(node.index())
+            .and_then(Option::as_ref)
+            .into_iter()
+            .flat_map(|entry| entry.adjacent_edges.iter())
+            .map(|&id| self.edge(id))
+    }
+
+    /// Edges entering `node`, none if it was removed
+    pub fn reverse_adjacent(&self, node: NodeId) -> impl Iterator<Item = GraphEdge<'_, D, E>> {
+        self.nodes
+            .get(node.index())
+            .and_then(Option::as_ref)
+            .into_iter()
+            .flat_map(|entry| entry.reverse_adjacent_edges.iter())
+            .map(|&id| self.edge(id))
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&T> {
+        Some(&self.nodes.get(id.index())?.as_ref()?.data)
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> Option<&mut T> {
+        Some(&mut self.nodes.get_mut(id.index())?.as_mut()?.data)
+    }
+
+    pub fn add_node(&mut self, value: T) -> NodeId {
+        let id = NodeId(
+            self.nodes
+                .len()
+                .try_into()
+                .expect("Graph supports at most u32::MAX nodes"),
+        );
+        self.nodes.push(Some(GraphNodeEntry {
+            data: value,
+            adjacent_edges: Vec::new(),
+            reverse_adjacent_edges: Vec::new(),
+        }));
+        id
+    }
+
+    /// Removes `id` with every edge entering or leaving it, returning its value
+    pub fn remove_node(&mut self, id: NodeId) -> Option<T> {
+        let entry = self.nodes.get_mut(id.index())?.take()?;
+        for edge in entry
+            .adjacent_edges
+            .iter()
+            .chain(entry.reverse_adjacent_edges.iter())
+        {
+            self.remove_edge(*edge);
         }
+        Some(entry.data)
     }
-}
 
-impl<T, D: GraphDistance> GraphNode<T, D> {
-    pub fn adjacent(&self) -> GraphNodeAdjacent<T, D> {
-        GraphNodeAdjacent {
-            nodes: self
-                .internal
-                .borrow()
-                .adjacent_nodes
-                .iter()
-                .map(GraphEdgeInternal::to_graph_edge)
-                .collect(),
+    pub fn add_edge(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        distance: D,
+        payload: E,
+    ) -> Result<EdgeId, GraphError> {
+        if let Some(&missing) = [from, to].iter().find(|&&id| !self.contains_node(id)) {
+            return Err(GraphError::MissingNode(missing));
+        }
+        let id = EdgeId(
+            self.edges
+                .len()
+                .try_into()
+                .expect("Graph supports at most u32::MAX edges"),
+        );
+        if let Some(entry) = self.nodes[from.index()].as_mut() {
+            entry.adjacent_edges.push(id);
         }
+        if let Some(entry) = self.nodes[to.index()].as_mut() {
+            entry.reverse_adjacent_edges.push(id);
+        }
+        if distance < D::zero() {
+            self.negative_edges += 1;
+        }
+        self.edges.push(Some(GraphEdgeEntry {
+            from,
+            to,
+            distance,
+            payload,
+        }));
+        Ok(id)
+    }
+
+    /// Removes `id`, returning its distance and payload
+    pub fn remove_edge(&mut self, id: EdgeId) -> Option<(D, E)> {
+        let entry = self.edges.get_mut(id.index())?.take()?;
+        if let Some(Some(from)) = self.nodes.get_mut(entry.from.index()) {
+            from.adjacent_edges.retain(|&edge| edge != id);
+        }
+        if let Some(Some(to)) = self.nodes.get_mut(entry.to.index()) {
+            to.reverse_adjacent_edges.retain(|&edge| edge != id);
+        }
+        if entry.distance < D::zero() {
+            self.negative_edges -= 1;
+        }
+        Some((entry.distance, entry.payload))
+    }
+
+    /// Replaces the distance of `id`, returning the previous one
+    pub fn set_weight(&mut self, id: EdgeId, distance: D) -> Result<D, GraphError> {
+        match self.edges.get_mut(id.index()) {
+            Some(Some(entry)) => {
+                let zero = D::zero();
+                if distance < zero {
+                    self.negative_edges += 1;
+                }
+                if entry.distance < zero {
+                    self.negative_edges -= 1;
+                }
+                Ok(std::mem::replace(&mut entry.distance, distance))
+            }
+            _ => Err(GraphError::MissingEdge(id)),
+        }
+    }
+
+    /// Whether any edge has a negative distance
+    pub fn has_negative_distance(&self) -> bool {
+        self.negative_edges > 0
     }
 
-    pub fn reverse_adjacent(&self) -> GraphNodeAdjacent<T, D> {
-        GraphNodeAdjacent {
+    /// Graph with the same node and edge ids, removed ones included, weighted by `distance`
+    pub(crate) fn reweighted<R: GraphDistance>(
+        &self,
+        distance: impl Fn(GraphEdge<'_, D, E>) -> R,
+    ) -> Graph<(), R> {
+        let zero = R::zero();
+        let mut negative_edges = 0;
+        let edges = (0..self.edges.len() as u32)
+            .map(|id| {
+                self.get_edge(EdgeId(id)).map(|edge| {
+                    let distance = distance(edge);
+                    if distance < zero {
+                        negative_edges += 1;
+                    }
+                    GraphEdgeEntry {
+                        from: edge.from,
+                        to: edge.to,
+                        distance,
+                        payload: (),
+                    }
+                })
+            })
+            .collect();
+        Graph {
             nodes: self
-                .internal
-                .borrow()
-                .reverse_adjacent_nodes
+                .nodes
                 .iter()
-                .map(GraphEdgeInternal::to_graph_edge)
+                .map(|entry| {
+                    entry.as_ref().map(|entry| GraphNodeEntry {
+                        data: (),
+                        adjacent_edges: entry.adjacent_edges.clone(),
+                        reverse_adjacent_edges: entry.reverse_adjacent_edges.clone(),
+                    })
+                })
                 .collect(),
+            edges,
+            negative_edges,
         }
     }
 }
 
-impl<T, D: GraphDistance> PartialEq for GraphNode<T, D> {
-    fn eq(&self, other: &Self) -> bool {
-        self.internal.as_ptr() == other.internal.as_ptr()
+impl<T, D: GraphDistance, E> Index<NodeId> for Graph<T, D, E> {
+    type Output = T;
+
+    /// Panics if `index` was removed
+    fn index(&self, index: NodeId) -> &T {
+        self.node(index).expect("Graph: removed node")
     }
 }
 
-impl<T, D: GraphDistance> Eq for GraphNode<T, D> {}
+/// Builds a [`Graph`] from an edge list, without allocating an adjacency matrix
+pub struct GraphBuilder<T, D: GraphDistance, E = ()> {
+    graph: Graph<T, D, E>,
+}
 
-impl<T, D: GraphDistance> PartialOrd for GraphNode<T, D> {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+impl<T, D: GraphDistance, E> Default for GraphBuilder<T, D, E> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-impl<T, D: GraphDistance> Ord for GraphNode<T, D> {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.internal.as_ptr().cmp(&other.internal.as_ptr())
+impl<T, D: GraphDistance, E> GraphBuilder<T, D, E> {
+    pub fn new() -> GraphBuilder<T, D, E> {
+        GraphBuilder {
+            graph: Graph {
+                nodes: Vec::new(),
+                edges: Vec::new(),
+                negative_edges: 0,
+            },
+        }
+    }
+
+    pub fn add_node(&mut self, value: T) -> NodeId {
+        self.graph.add_node(value)
+    }
+
+    /// Adds an edge from `from` to `to`; panics if either node was not added to this builder
+    pub fn add_edge(&mut self, from: NodeId, to: NodeId, distance: D, payload: E) -> EdgeId {
+        self.graph
+            .add_edge(from, to, distance, payload)
+            .expect("GraphBuilder::add_edge: node out of range")
+    }
+
+    /// Adds an edge in both directions between `a` and `b`
+    pub fn add_undirected_edge(&mut self, a: NodeId, b: NodeId, distance: D, payload: E)
+    where
+        E: Clone,
+    {
+        self.add_edge(a, b, distance.clone(), payload.clone());
+        self.add_edge(b, a, distance, payload);
     }
-}
 
-impl<T, D: GraphDistance> Hash for GraphNode<T, D> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.internal.as_ptr().hash(state);
+    pub fn build(self) -> Graph<T, D, E> {
+        self.graph
     }
 }
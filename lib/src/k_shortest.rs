@@ -0,0 +1,240 @@
+use std::collections::HashSet;
+
+use crate::{
+    check_distances,
+    graph::{EdgeId, Graph, GraphDistance, GraphEdge, NodeId},
+    search, NegativeDistance,
+};
+
+type EdgeFilter<'a, D, E> = Box<dyn Fn(&GraphEdge<'_, D, E>) -> bool + 'a>;
+
+/// Loopless paths from one node to another in increasing order of cost, found lazily with
+/// Yen's algorithm
+pub struct ShortestPaths<'a, T, D: GraphDistance, E> {
+    graph: &'a Graph<T, D, E>,
+    from: NodeId,
+    to: NodeId,
+    /// paths already returned, in order
+    found: Vec<Vec<EdgeId>>,
+    /// paths that deviate from a returned one, not returned yet
+    candidates: Vec<(D, Vec<EdgeId>)>,
+    /// edges paths may take, entering only accepted nodes
+    allowed: EdgeFilter<'a, D, E>,
+}
+
+/// Every loopless path from `from` to `to`, cheapest first; ties keep the path with fewer edges
+/// first; fails on a negative distance
+pub fn shortest_paths<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    from: NodeId,
+    to: NodeId,
+) -> Result<ShortestPaths<'_, T, D, E>, NegativeDistance> {
+    shortest_paths_filtered(graph, from, to, |_| true, |_| true)
+}
+
+/// [`shortest_paths`] entering only nodes `node` accepts, over edges `edge` accepts
+pub fn shortest_paths_filtered<'a, T, D: GraphDistance, E>(
+    graph: &'a Graph<T, D, E>,
+    from: NodeId,
+    to: NodeId,
+    node: impl Fn(NodeId) -> bool + 'a,
+    edge: impl Fn(&GraphEdge<'_, D, E>) -> bool + 'a,
+) -> Result<ShortestPaths<'a, T, D, E>, NegativeDistance> {
+    let allowed: EdgeFilter<'a, D, E> = Box::new(move |e| node(e.to) && edge(e));
+    // every search only narrows these edges down
+    check_distances(graph, &allowed)?;
+    Ok(ShortestPaths {
+        graph,
+        from,
+        to,
+        found: Vec::new(),
+        candidates: Vec::new(),
+        allowed,
+    })
+}
+
+/// Up to `k` loopless paths from `from` to `to`, cheapest first; fails on a negative distance
+pub fn k_shortest_paths<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    from: NodeId,
+    to: NodeId,
+    k: usize,
+) -> Result<Vec<Vec<GraphEdge<'_, D, E>>>, NegativeDistance> {
+    Ok(shortest_paths(graph, from, to)?.take(k).collect())
+}
+
+impl<T, D: GraphDistance, E> ShortestPaths<'_, T, D, E> {
+    fn cost(&self, path: &[EdgeId]) -> D {
+        path.iter().fold(D::zero(), |sum, &edge| {
+            sum + self.graph.edge(edge).distance.clone()
+        })
+    }
+
+    /// Adds a candidate for every way of leaving the last found path at one of its nodes
+    fn add_deviations(&mut self) {
+        let last = self.found.last().unwrap();
+        for spur_index in 0..last.len() {
+            let root = &last[..spur_index];
+            let spur = match root.last() {
+                Some(&edge) => self.graph.edge(edge).to,
+                None => self.from,
+            };
+            let removed_edges = self
+                .found
+                .iter()
+                .filter(|path| path.len() > spur_index && path[..spur_index] == *root)
+                .map(|path| path[spur_index])
+                .collect::<HashSet<_>>();
+            let removed_nodes = root
+                .iter()
+                .map(|&edge| self.graph.edge(edge).from)
+                .collect::<HashSet<_>>();
+
+            let tree = search(
+                self.graph,
+                spur,
+                Some(self.to),
+                |_| D::zero(),
+                |edge| {
+                    !removed_edges.contains(&edge.id)
+                        && !removed_nodes.contains(&edge.to)
+                        && (self.allowed)(edge)
+                },
+            );
+            let Some(spur_path) = tree.path_to(self.graph, self.to) else {
+                continue;
+            };
+            let path = root
+                .iter()
+                .copied()
+                .chain(spur_path.iter().map(|edge| edge.id))
+                .collect::<Vec<_>>();
+            if !self
+                .candidates
+                .iter()
+                .any(|(_, candidate)| *candidate == path)
+            {
+                self.candidates.push((self.cost(&path), path));
+            }
+        }
+    }
+}
+
+impl<'a, T, D: GraphDistance, E> Iterator for ShortestPaths<'a, T, D, E> {
+    type Item = Vec<GraphEdge<'a, D, E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let path = if self.found.is_empty() {
+            let tree = search(
+                self.graph,
+                self.from,
+                Some(self.to),
+                |_| D::zero(),
+                &self.allowed,
+            );
+            tree.path_to(self.graph, self.to)?
+                .iter()
+                .map(|edge| edge.id)
+                .collect()
+        } else {
+            if self.found.last().unwrap().is_empty() {
+                // `from == to`, and the empty path is the only loopless one
+                return None;
+            }
+            self.add_deviations();
+            let (index, _) = self.candidates.iter().enumerate().min_by(
+                |(_, (a_cost, a)), (_, (b_cost, b))| a_cost.cmp(b_cost).then(a.len().cmp(&b.len())),
+            )?;
+            self.candidates.swap_remove(index).1
+        };
+        let graph = self.graph;
+        let result = path.iter().map(|&edge| graph.edge(edge)).collect();
+        self.found.push(path);
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::GraphBuilder;
+
+    use super::*;
+
+    fn names(path: &[GraphEdge<'_, u32, ()>], graph: &Graph<char, u32>) -> String {
+        let mut result = path
+            .first()
+            .map_or(String::new(), |edge| graph[edge.from].to_string());
+        result.extend(path.iter().map(|edge| graph[edge.to]));
+        result
+    }
+
+    #[test]
+    fn yen_example() {
+        // the example graph from Wikipedia's article on Yen's algorithm
+        let mut builder = GraphBuilder::new();
+        let nodes = "CDEFGH"
+            .chars()
+            .map(|name| builder.add_node(name))
+            .collect::<Vec<_>>();
+        for (from, to, distance) in [
+            (0, 1, 3),
+            (0, 2, 2),
+            (1, 3, 4),
+            (2, 1, 1),
+            (2, 3, 2),
+            (2, 4, 3),
+            (3, 4, 2),
+            (3, 5, 1),
+            (4, 5, 2),
+        ] {
+            builder.add_edge(nodes[from], nodes[to], distance, ());
+        }
+        let graph = builder.build();
+
+        let paths = k_shortest_paths(&graph, nodes[0], nodes[5], 3).unwrap();
+        assert_eq!(
+            paths
+                .iter()
+                .map(|path| names(path, &graph))
+                .collect::<Vec<_>>(),
+            vec!["CEFH", "CEGH", "CDFH"]
+        );
+
+        let all = shortest_paths(&graph, nodes[0], nodes[5])
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(all.len(), 7);
+        let costs = all
+            .iter()
+            .map(|path| path.iter().map(|edge| *edge.distance).sum::<u32>())
+            .collect::<Vec<_>>();
+        assert!(costs.windows(2).all(|pair| pair[0] <= pair[1]));
+        for path in all.iter() {
+            let mut visited = HashSet::new();
+            assert!(visited.insert(path[0].from));
+            assert!(path.iter().all(|edge| visited.insert(edge.to)));
+        }
+
+        assert_eq!(k_shortest_paths(&graph, nodes[5], nodes[0], 3), Ok(vec![]));
+        assert_eq!(
+            k_shortest_paths(&graph, nodes[0], nodes[0], 3),
+            Ok(vec![vec![]])
+        );
+
+        // without E, nor the edge from F to H
+        let filtered = shortest_paths_filtered(
+            &graph,
+            nodes[0],
+            nodes[5],
+            |node| node != nodes[2],
+            |edge| (edge.from, edge.to) != (nodes[3], nodes[5]),
+        )
+        .unwrap();
+        assert_eq!(
+            filtered
+                .map(|path| names(&path, &graph))
+                .collect::<Vec<_>>(),
+            vec!["CDFGH"]
+        );
+    }
+}
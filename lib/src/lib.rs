@@ -1,63 +1,262 @@
-use std::collections::HashMap;
+use std::{error::Error, fmt};
 
-use graph::{GraphDistance, GraphEdge, GraphNode};
+use graph::{EdgeId, Graph, GraphDistance, GraphEdge, NodeId};
 use priority_queue::PriorityQueue;
 
+pub mod all_pairs;
+pub mod batch;
+pub mod bellman_ford;
+pub mod bidirectional;
+pub mod connection_scan;
+pub mod export;
 pub mod graph;
+pub mod k_shortest;
+pub mod pareto;
 pub mod priority_queue;
+pub mod via;
 
-pub fn dijkstra<T, D: GraphDistance>(
-    from: GraphNode<T, D>,
-    to: GraphNode<T, D>,
-) -> Option<Vec<GraphEdge<T, D>>> {
-    let mut to_visit = PriorityQueue::<GraphNode<T, D>, D, GraphEdge<T, D>>::new();
-    let mut visited = HashMap::<GraphNode<T, D>, Option<GraphEdge<T, D>>>::new();
+/// Shortest paths from one node to every node reached by the search
+#[derive(Debug, Clone)]
+pub struct ShortestPathTree<D> {
+    from: NodeId,
+    distances: Vec<Option<D>>,
+    /// edge each reached node was entered by, None for the root and unreached nodes
+    predecessors: Vec<Option<EdgeId>>,
+    /// reached nodes in increasing distance order, starting with the root
+    order: Vec<NodeId>,
+}
+
+impl<D: GraphDistance> ShortestPathTree<D> {
+    /// Node the tree grows from
+    pub fn root(&self) -> NodeId {
+        self.from
+    }
+
+    /// Shortest distance from the root to `node`, None if unreachable
+    pub fn distance(&self, node: NodeId) -> Option<&D> {
+        self.distances[node.index()].as_ref()
+    }
+
+    /// Last edge of the shortest path to `node`, None for the root and unreachable nodes
+    pub fn predecessor(&self, node: NodeId) -> Option<EdgeId> {
+        self.predecessors[node.index()]
+    }
+
+    /// Reachable nodes, nearest first, starting with the root
+    pub fn reachable(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.order.iter().copied()
+    }
 
-    visited.insert(from.clone(), None);
-    for edge in from.adjacent().nodes {
-        to_visit.push(edge.to.clone(), edge.distance.clone(), edge);
+    /// Shortest path from the root to `to`, None if unreachable
+    pub fn path_to<'a, T, E>(
+        &self,
+        graph: &'a Graph<T, D, E>,
+        to: NodeId,
+    ) -> Option<Vec<GraphEdge<'a, D, E>>> {
+        self.distance(to)?;
+        let mut last_move = self.predecessor(to);
+        let mut result = Vec::new();
+        while let Some(edge) = last_move {
+            let edge = graph.edge(edge);
+            last_move = self.predecessor(edge.from);
+            result.push(edge);
+        }
+        result.reverse();
+        Some(result)
     }
+}
+
+pub(crate) type Path<'a, D, E> = Vec<GraphEdge<'a, D, E>>;
+
+/// Counters describing the work done by a search
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// nodes taken out of the queue and whose edges were relaxed
+    pub expanded: usize,
+}
+
+/// Edge with a negative distance, which Dijkstra and A* cannot search;
+/// see [`shortest_path`](bellman_ford::shortest_path) and [`johnson`](all_pairs::johnson)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NegativeDistance(pub EdgeId);
+
+impl fmt::Display for NegativeDistance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Negative distance on edge {}", self.0.index())
+    }
+}
 
-    while let Some((node_to_visit, new_distance, new_move)) = to_visit.pop_by_priority() {
-        visited.insert(node_to_visit.clone(), Some(new_move));
-        if node_to_visit == to {
+impl Error for NegativeDistance {}
+
+/// Fails on the first edge `allowed` accepts whose distance is negative, so that a search over
+/// the same edges never meets one; only scans the edges when the graph has a negative one
+pub(crate) fn check_distances<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    allowed: impl Fn(&GraphEdge<'_, D, E>) -> bool,
+) -> Result<(), NegativeDistance> {
+    if !graph.has_negative_distance() {
+        return Ok(());
+    }
+    let zero = D::zero();
+    match graph
+        .edges()
+        .find(|edge| *edge.distance < zero && allowed(edge))
+    {
+        Some(edge) => Err(NegativeDistance(edge.id)),
+        None => Ok(()),
+    }
+}
+
+/// A* from `from` over the edges `allowed` accepts, stopping once `to` is reached if given;
+/// plain Dijkstra when `heuristic` is zero; those edges must have passed [`check_distances`]
+pub(crate) fn search<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    from: NodeId,
+    to: Option<NodeId>,
+    heuristic: impl Fn(NodeId) -> D,
+    allowed: impl Fn(&GraphEdge<'_, D, E>) -> bool,
+) -> ShortestPathTree<D> {
+    // ordered by distance + heuristic, carrying the distance itself
+    let mut to_visit = PriorityQueue::<NodeId, D, (D, Option<EdgeId>)>::new();
+    let mut result = ShortestPathTree {
+        from,
+        distances: vec![None; graph.length()],
+        predecessors: vec![None; graph.length()],
+        order: Vec::new(),
+    };
+
+    to_visit.push(from, heuristic(from), (D::zero(), None));
+    while let Some((node_to_visit, _, (distance, last_move))) = to_visit.pop_by_priority() {
+        result.distances[node_to_visit.index()] = Some(distance.clone());
+        result.predecessors[node_to_visit.index()] = last_move;
+        result.order.push(node_to_visit);
+        if Some(node_to_visit) == to {
             break;
         }
-        for edge in node_to_visit.adjacent().nodes {
-            if !visited.contains_key(&edge.to) {
+        for edge in graph.adjacent(node_to_visit) {
+            if result.distances[edge.to.index()].is_none() && allowed(&edge) {
+                let distance = distance.clone() + edge.distance.clone();
                 to_visit.push(
-                    edge.to.clone(),
-                    new_distance.clone() + edge.distance.clone(),
-                    edge,
+                    edge.to,
+                    distance.clone() + heuristic(edge.to),
+                    (distance, Some(edge.id)),
                 );
             }
         }
     }
+    result
+}
 
-    if let Some(last_move) = visited.get(&to) {
-        Some(if let Some(last_move) = last_move {
-            let mut last_move = Some(last_move.clone());
-            let mut result = Vec::new();
-            while let Some(edge) = last_move {
-                result.push(edge.clone());
-                last_move = visited.get(&edge.from).unwrap().clone();
-            }
-            result.reverse();
-            result
-        } else {
-            vec![]
-        })
-    } else {
-        None
-    }
+/// Distances and shortest paths from `from` to every reachable node, in one run;
+/// fails on a negative distance, see [`bellman_ford`](bellman_ford::bellman_ford)
+pub fn shortest_path_tree<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    from: NodeId,
+) -> Result<ShortestPathTree<D>, NegativeDistance> {
+    shortest_path_tree_filtered(graph, from, |_| true, |_| true)
+}
+
+/// [`shortest_path_tree`] entering only nodes `node` accepts, over edges `edge` accepts
+pub fn shortest_path_tree_filtered<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    from: NodeId,
+    node: impl Fn(NodeId) -> bool,
+    edge: impl Fn(&GraphEdge<'_, D, E>) -> bool,
+) -> Result<ShortestPathTree<D>, NegativeDistance> {
+    let allowed = |e: &GraphEdge<'_, D, E>| node(e.to) && edge(e);
+    check_distances(graph, allowed)?;
+    Ok(search(graph, from, None, |_| D::zero(), allowed))
+}
+
+/// Shortest path from `from` to `to`; fails on a negative distance, see
+/// [`shortest_path`](bellman_ford::shortest_path)
+pub fn dijkstra<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    from: NodeId,
+    to: NodeId,
+) -> Result<Option<Vec<GraphEdge<'_, D, E>>>, NegativeDistance> {
+    Ok(dijkstra_with_stats(graph, from, to)?.0)
+}
+
+/// [`dijkstra`], also reporting how many nodes were expanded
+pub fn dijkstra_with_stats<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    from: NodeId,
+    to: NodeId,
+) -> Result<(Option<Path<'_, D, E>>, SearchStats), NegativeDistance> {
+    astar_with_stats(graph, from, to, |_| D::zero())
+}
+
+/// [`dijkstra`] entering only nodes `node` accepts, over edges `edge` accepts, to route around
+/// parts of a graph without changing it; `from` itself is not checked
+pub fn dijkstra_filtered<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    from: NodeId,
+    to: NodeId,
+    node: impl Fn(NodeId) -> bool,
+    edge: impl Fn(&GraphEdge<'_, D, E>) -> bool,
+) -> Result<Option<Vec<GraphEdge<'_, D, E>>>, NegativeDistance> {
+    astar_filtered(graph, from, to, |_| D::zero(), node, edge)
+}
+
+/// Shortest path from `from` to `to`, exploring nodes in order of distance plus
+/// `heuristic`, an estimate of the remaining distance to `to`;
+/// the path is the shortest as long as `heuristic` never overestimates and never
+/// drops by more than an edge's distance along that edge; fails on a negative distance
+pub fn astar<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    from: NodeId,
+    to: NodeId,
+    heuristic: impl Fn(NodeId) -> D,
+) -> Result<Option<Vec<GraphEdge<'_, D, E>>>, NegativeDistance> {
+    Ok(astar_with_stats(graph, from, to, heuristic)?.0)
+}
+
+/// [`astar`], also reporting how many nodes were expanded
+pub fn astar_with_stats<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    from: NodeId,
+    to: NodeId,
+    heuristic: impl Fn(NodeId) -> D,
+) -> Result<(Option<Path<'_, D, E>>, SearchStats), NegativeDistance> {
+    check_distances(graph, |_| true)?;
+    let tree = search(graph, from, Some(to), heuristic, |_| true);
+    let stats = SearchStats {
+        expanded: tree.order.len(),
+    };
+    Ok((tree.path_to(graph, to), stats))
+}
+
+/// [`astar`] entering only nodes `node` accepts, over edges `edge` accepts
+pub fn astar_filtered<T, D: GraphDistance, E>(
+    graph: &Graph<T, D, E>,
+    from: NodeId,
+    to: NodeId,
+    heuristic: impl Fn(NodeId) -> D,
+    node: impl Fn(NodeId) -> bool,
+    edge: impl Fn(&GraphEdge<'_, D, E>) -> bool,
+) -> Result<Option<Vec<GraphEdge<'_, D, E>>>, NegativeDistance> {
+    let allowed = |e: &GraphEdge<'_, D, E>| node(e.to) && edge(e);
+    check_distances(graph, allowed)?;
+    Ok(search(graph, from, Some(to), heuristic, allowed).path_to(graph, to))
 }
 
 #[cfg(test)]
 mod tests {
-    use graph::{Graph, GraphDistanceF32};
+    use graph::{GraphBuilder, GraphDistanceF32, GraphError};
 
     use super::*;
 
+    fn hops<D: Clone, E>(
+        path: Result<Option<Vec<GraphEdge<'_, D, E>>>, NegativeDistance>,
+    ) -> Option<Vec<(NodeId, NodeId, D)>> {
+        path.unwrap().map(|path| {
+            path.into_iter()
+                .map(|edge| (edge.from, edge.to, edge.distance.clone()))
+                .collect()
+        })
+    }
+
     #[test]
     fn simplest_case() {
         let adjacent_matrix = vec![
@@ -68,51 +267,387 @@ mod tests {
         let values = vec!["1", "2", "3"];
         let graph = Graph::new(values, adjacent_matrix).unwrap();
 
-        let node0 = graph[0].clone();
-        let node1 = graph[1].clone();
-        let node2 = graph[2].clone();
+        let node0 = graph.node_id(0).unwrap();
+        let node1 = graph.node_id(1).unwrap();
+        let node2 = graph.node_id(2).unwrap();
 
         assert_eq!(
-            dijkstra(node0.clone(), node1.clone()),
-            Some(vec![GraphEdge {
-                distance: GraphDistanceF32::new(1f32),
-                from: node0.clone(),
-                to: node1.clone()
-            }])
+            hops(dijkstra(&graph, node0, node1)),
+            Some(vec![(node0, node1, GraphDistanceF32::new(1f32))])
         );
 
         assert_eq!(
-            dijkstra(node1.clone(), node0.clone()),
-            Some(vec![GraphEdge {
-                distance: GraphDistanceF32::new(2f32),
-                from: node1.clone(),
-                to: node0.clone()
-            }])
+            hops(dijkstra(&graph, node1, node0)),
+            Some(vec![(node1, node0, GraphDistanceF32::new(2f32))])
         );
 
-        assert_eq!(dijkstra(node0.clone(), node0.clone()), Some(vec![]));
+        assert_eq!(dijkstra(&graph, node0, node0), Ok(Some(vec![])));
 
-        assert_eq!(dijkstra(node0.clone(), node2.clone()), None);
+        assert_eq!(dijkstra(&graph, node0, node2), Ok(None));
 
         assert_eq!(
-            dijkstra(node2.clone(), node1.clone()),
+            hops(dijkstra(&graph, node2, node1)),
             Some(vec![
-                GraphEdge {
-                    distance: GraphDistanceF32::new(3f32),
-                    from: node2.clone(),
-                    to: node0.clone()
-                },
-                GraphEdge {
-                    distance: GraphDistanceF32::new(1f32),
-                    from: node0.clone(),
-                    to: node1.clone()
-                }
+                (node2, node0, GraphDistanceF32::new(3f32)),
+                (node0, node1, GraphDistanceF32::new(1f32)),
             ])
         );
     }
 
     #[test]
-    fn should_work() {
-        //
+    fn parallel_edges_with_payload() {
+        let graph = Graph::from_edges(
+            vec!["1", "2"],
+            vec![
+                (0, 1, 5u32, "slow"),
+                (0, 1, 2u32, "fast"),
+                (1, 0, 1u32, "back"),
+            ],
+        )
+        .unwrap();
+
+        let node0 = graph.node_id(0).unwrap();
+        let node1 = graph.node_id(1).unwrap();
+
+        assert_eq!(graph.adjacent(node0).count(), 2);
+        assert_eq!(graph.reverse_adjacent(node1).count(), 2);
+        assert_eq!(
+            dijkstra(&graph, node0, node1)
+                .unwrap()
+                .unwrap()
+                .iter()
+                .map(|edge| *edge.payload)
+                .collect::<Vec<_>>(),
+            vec!["fast"]
+        );
+        assert_eq!(
+            Graph::from_edges(vec!["1"], vec![(0, 0, 1u32, ()), (0, 1, 1u32, ())]).err(),
+            Some(GraphError::NodeOutOfRange {
+                edge: 1,
+                node: 1,
+                length: 1
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_matrix() {
+        assert_eq!(
+            Graph::new(vec!["1", "2"], vec![vec![None, Some(1u32)]]).err(),
+            Some(GraphError::MatrixSizeMismatch {
+                expected: 2,
+                row: None,
+                len: 1
+            })
+        );
+        assert_eq!(
+            Graph::new(vec!["1", "2"], vec![vec![None, Some(1u32)], vec![None]]).err(),
+            Some(GraphError::MatrixSizeMismatch {
+                expected: 2,
+                row: Some(1),
+                len: 1
+            })
+        );
+        assert_eq!(
+            Graph::new(
+                vec!["1", "2"],
+                vec![vec![None, None], vec![None, Some(1u32)]]
+            )
+            .err(),
+            Some(GraphError::DiagonalEdge { node: 1 })
+        );
+    }
+
+    #[test]
+    fn builder() {
+        let mut builder = GraphBuilder::new();
+        let a = builder.add_node("a");
+        let b = builder.add_node("b");
+        let c = builder.add_node("c");
+        builder.add_undirected_edge(a, b, 1u32, ());
+        builder.add_edge(b, c, 1u32, ());
+        builder.add_edge(a, c, 3u32, ());
+        let graph = builder.build();
+
+        assert_eq!(graph.length(), 3);
+        assert_eq!(graph.edge_count(), 4);
+        assert_eq!(graph[a], "a");
+        assert_eq!(
+            dijkstra(&graph, a, c).unwrap().map(|path| path.len()),
+            Some(2)
+        );
+        assert_eq!(dijkstra(&graph, c, a).unwrap().map(|path| path.len()), None);
+        assert_eq!(
+            dijkstra(&graph, b, a).unwrap().map(|path| path.len()),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn mutation() {
+        let mut graph = Graph::from_edges(
+            vec!["a", "b", "c"],
+            vec![(0, 1, 1u32, ()), (1, 2, 1, ()), (0, 2, 5, ())],
+        )
+        .unwrap();
+        let (a, b, c) = (
+            graph.node_id(0).unwrap(),
+            graph.node_id(1).unwrap(),
+            graph.node_id(2).unwrap(),
+        );
+        assert_eq!(
+            dijkstra(&graph, a, c).unwrap().map(|path| path.len()),
+            Some(2)
+        );
+
+        assert_eq!(graph.remove_node(b), Some("b"));
+        assert_eq!(graph.remove_node(b), None);
+        assert_eq!((graph.node_count(), graph.edge_count()), (2, 1));
+        assert_eq!(graph.node_ids().collect::<Vec<_>>(), vec![a, c]);
+        assert_eq!(graph.node_id(1), None);
+        assert_eq!(graph.adjacent(b).count(), 0);
+        assert_eq!(graph.reverse_adjacent(c).count(), 1);
+        assert_eq!(
+            graph.add_edge(a, b, 1, ()).err(),
+            Some(GraphError::MissingNode(b))
+        );
+        assert_eq!(hops(dijkstra(&graph, a, c)), Some(vec![(a, c, 5)]));
+
+        let d = graph.add_node("d");
+        assert_eq!(d.index(), 3);
+        let ad = graph.add_edge(a, d, 1, ()).unwrap();
+        let dc = graph.add_edge(d, c, 1, ()).unwrap();
+        assert_eq!(
+            hops(dijkstra(&graph, a, c)),
+            Some(vec![(a, d, 1), (d, c, 1)])
+        );
+        assert_eq!(graph.set_weight(dc, 10), Ok(1));
+        assert_eq!(hops(dijkstra(&graph, a, c)), Some(vec![(a, c, 5)]));
+        assert_eq!(graph.remove_edge(ad), Some((1, ())));
+        assert_eq!(graph.remove_edge(ad), None);
+        assert_eq!(graph.get_edge(ad).map(|edge| edge.id), None);
+        assert_eq!(graph.set_weight(ad, 1), Err(GraphError::MissingEdge(ad)));
+        assert_eq!(graph.adjacent(a).count(), 1);
+        assert_eq!(graph.reverse_adjacent(d).count(), 0);
+
+        let all_pairs = all_pairs::johnson(&graph, 1).unwrap();
+        assert_eq!(all_pairs.distance(a, c), Some(&5));
+        assert_eq!(all_pairs.distance(d, c), Some(&10));
+        assert_eq!(
+            all_pairs.path(&graph, a, c).map(|path| path[0].id),
+            dijkstra(&graph, a, c).unwrap().map(|path| path[0].id)
+        );
+    }
+
+    #[test]
+    fn filtered() {
+        let graph = Graph::from_edges(
+            vec!["a", "b", "c", "d"],
+            vec![
+                (0, 1, 1u32, "fast"),
+                (1, 3, 1, "fast"),
+                (0, 2, 2, "fast"),
+                (2, 3, 2, "fast"),
+                (0, 3, 9, "slow"),
+            ],
+        )
+        .unwrap();
+        let nodes = graph.node_ids().collect::<Vec<_>>();
+        let (a, b, c, d) = (nodes[0], nodes[1], nodes[2], nodes[3]);
+        let cost = |path: Result<Option<Vec<GraphEdge<'_, u32, &str>>>, NegativeDistance>| {
+            path.unwrap()
+                .map(|path| path.iter().map(|edge| *edge.distance).sum::<u32>())
+        };
+
+        assert_eq!(cost(dijkstra(&graph, a, d)), Some(2));
+        assert_eq!(
+            cost(dijkstra_filtered(&graph, a, d, |node| node != b, |_| true)),
+            Some(4)
+        );
+        assert_eq!(
+            cost(dijkstra_filtered(
+                &graph,
+                a,
+                d,
+                |node| node != b,
+                |edge| *edge.payload != "slow"
+            )),
+            Some(4)
+        );
+        assert_eq!(
+            cost(astar_filtered(
+                &graph,
+                a,
+                d,
+                |_| 0,
+                |node| node != b && node != c,
+                |_| true
+            )),
+            Some(9)
+        );
+        assert_eq!(
+            dijkstra_filtered(&graph, a, d, |node| node != d, |_| true)
+                .unwrap()
+                .map(|path| path.len()),
+            None
+        );
+        let tree =
+            shortest_path_tree_filtered(&graph, a, |_| true, |edge| *edge.distance < 2).unwrap();
+        assert_eq!(
+            nodes
+                .iter()
+                .map(|&node| tree.distance(node).copied())
+                .collect::<Vec<_>>(),
+            vec![Some(0), Some(1), None, Some(2)]
+        );
+    }
+
+    #[test]
+    fn negative_distance_beyond_target() {
+        // the target is settled before the negative edge is ever scanned
+        let graph = Graph::from_edges(
+            vec!["a", "b", "t"],
+            vec![(0, 2, 5i32, ()), (0, 1, 6, ()), (1, 2, -10, ())],
+        )
+        .unwrap();
+        let nodes = graph.node_ids().collect::<Vec<_>>();
+        let (a, b, t) = (nodes[0], nodes[1], nodes[2]);
+        let negative = graph.adjacent(b).next().unwrap().id;
+
+        assert_eq!(dijkstra(&graph, a, t), Err(NegativeDistance(negative)));
+        assert_eq!(
+            astar(&graph, a, t, |_| 0).err(),
+            Some(NegativeDistance(negative))
+        );
+        assert_eq!(
+            shortest_path_tree(&graph, a).err(),
+            Some(NegativeDistance(negative))
+        );
+        assert_eq!(
+            bidirectional::bidirectional_dijkstra(&graph, a, t),
+            Err(NegativeDistance(negative))
+        );
+        assert_eq!(
+            bellman_ford::shortest_path(&graph, a, t)
+                .unwrap()
+                .map(|path| path.len()),
+            Some(2)
+        );
+
+        // an edge filtered out cannot fail the search
+        assert_eq!(
+            hops(dijkstra_filtered(
+                &graph,
+                a,
+                t,
+                |_| true,
+                |edge| edge.id != negative
+            )),
+            Some(vec![(a, t, 5)])
+        );
+        assert_eq!(
+            hops(dijkstra_filtered(&graph, a, t, |node| node != t, |_| true)),
+            None
+        );
+
+        // the graph keeps track of its negative edges as they change
+        let mut graph = graph;
+        assert!(graph.has_negative_distance());
+        assert_eq!(graph.set_weight(negative, 1), Ok(-10));
+        assert!(!graph.has_negative_distance());
+        assert_eq!(hops(dijkstra(&graph, a, t)), Some(vec![(a, t, 5)]));
+        let edge = graph.add_edge(a, b, -1, ()).unwrap();
+        assert_eq!(dijkstra(&graph, a, t), Err(NegativeDistance(edge)));
+        graph.remove_edge(edge);
+        assert!(!graph.has_negative_distance());
+    }
+
+    #[test]
+    fn tree_matches_dijkstra() {
+        let mut builder = GraphBuilder::new();
+        let nodes = (0..5).map(|i| builder.add_node(i)).collect::<Vec<_>>();
+        builder.add_edge(nodes[0], nodes[1], 4u32, ());
+        builder.add_edge(nodes[0], nodes[2], 1u32, ());
+        builder.add_edge(nodes[2], nodes[1], 2u32, ());
+        builder.add_edge(nodes[1], nodes[3], 5u32, ());
+        builder.add_edge(nodes[4], nodes[0], 1u32, ());
+        let graph = builder.build();
+
+        let tree = shortest_path_tree(&graph, nodes[0]).unwrap();
+        assert_eq!(tree.root(), nodes[0]);
+        assert_eq!(
+            nodes
+                .iter()
+                .map(|&node| tree.distance(node).copied())
+                .collect::<Vec<_>>(),
+            vec![Some(0), Some(3), Some(1), Some(8), None]
+        );
+        assert_eq!(
+            tree.reachable().collect::<Vec<_>>(),
+            vec![nodes[0], nodes[2], nodes[1], nodes[3]]
+        );
+        assert_eq!(tree.predecessor(nodes[0]), None);
+        for &node in nodes.iter() {
+            assert_eq!(
+                Ok(tree.path_to(&graph, node)),
+                dijkstra(&graph, nodes[0], node)
+            );
+        }
+    }
+
+    #[test]
+    fn astar_on_grid() {
+        let size = 10;
+        let mut builder = GraphBuilder::new();
+        let nodes = (0..size * size)
+            .map(|i| builder.add_node((i % size, i / size)))
+            .collect::<Vec<_>>();
+        for y in 0..size {
+            for x in 0..size {
+                if x + 1 < size {
+                    builder.add_undirected_edge(
+                        nodes[y * size + x],
+                        nodes[y * size + x + 1],
+                        1u32,
+                        (),
+                    );
+                }
+                if y + 1 < size {
+                    builder.add_undirected_edge(
+                        nodes[y * size + x],
+                        nodes[(y + 1) * size + x],
+                        1u32,
+                        (),
+                    );
+                }
+            }
+        }
+        let graph = builder.build();
+        let graph = &graph;
+        let manhattan = |to: NodeId| {
+            let (to_x, to_y) = graph[to];
+            move |node: NodeId| {
+                let (x, y) = graph[node];
+                (x.abs_diff(to_x) + y.abs_diff(to_y)) as u32
+            }
+        };
+
+        for (from, to) in [(0, 99), (45, 3), (12, 12), (90, 9)] {
+            let (from, to) = (nodes[from], nodes[to]);
+            let (expected, dijkstra_stats) = dijkstra_with_stats(graph, from, to).unwrap();
+            let (path, astar_stats) = astar_with_stats(graph, from, to, manhattan(to)).unwrap();
+            assert_eq!(path.map(|path| path.len()), expected.map(|path| path.len()));
+            assert!(astar_stats.expanded <= dijkstra_stats.expanded);
+        }
+        let (_, dijkstra_stats) = dijkstra_with_stats(graph, nodes[0], nodes[9]).unwrap();
+        let (_, astar_stats) =
+            astar_with_stats(graph, nodes[0], nodes[9], manhattan(nodes[9])).unwrap();
+        assert!(astar_stats.expanded < dijkstra_stats.expanded);
+    }
+
+    #[test]
+    fn graph_is_send_and_sync() {
+        fn assert_send_sync<X: Send + Sync>() {}
+        assert_send_sync::<Graph<String, GraphDistanceF32, String>>();
     }
 }
@@ -0,0 +1,189 @@
+use std::ops::Add;
+
+use crate::{
+    graph::{EdgeId, Graph, GraphDistance, GraphEdge, NodeId},
+    priority_queue::PriorityQueue,
+    NegativeDistance,
+};
+
+/// Cost made of `N` criteria, added criterion by criterion; only partially ordered by
+/// [`CostVector::dominates`], while `Ord` compares lexicographically
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CostVector<D, const N: usize>(pub [D; N]);
+
+impl<D: GraphDistance, const N: usize> CostVector<D, N> {
+    pub fn zero() -> Self {
+        CostVector(std::array::from_fn(|_| D::zero()))
+    }
+
+    /// Whether `self` is no worse than `other` in every criterion and better in at least one
+    pub fn dominates(&self, other: &Self) -> bool {
+        self.0.iter().zip(other.0.iter()).all(|(a, b)| a <= b)
+            && self.0.iter().zip(other.0.iter()).any(|(a, b)| a < b)
+    }
+
+    /// Whether `self` is no worse than `other` in every criterion
+    fn covers(&self, other: &Self) -> bool {
+        self.0.iter().zip(other.0.iter()).all(|(a, b)| a <= b)
+    }
+}
+
+impl<D: GraphDistance, const N: usize> Add for CostVector<D, N> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        let mut rhs = rhs.0.into_iter();
+        CostVector(self.0.map(|a| a + rhs.next().unwrap()))
+    }
+}
+
+/// Paths with their costs, as returned by [`pareto_paths`]
+pub type ParetoFront<'a, D, E, C, const N: usize> =
+    Vec<(CostVector<C, N>, Vec<GraphEdge<'a, D, E>>)>;
+
+/// Partial path found by the search, linked to the label it extends
+struct Label<D, const N: usize> {
+    node: NodeId,
+    cost: CostVector<D, N>,
+    previous: Option<(usize, EdgeId)>,
+}
+
+/// Pareto-optimal paths from `from` to `to`: one path for every cost no other path dominates,
+/// where `cost` gives the cost of each edge; sorted lexicographically by cost;
+/// fails on an edge with a negative criterion
+pub fn pareto_paths<'a, T, D: GraphDistance, E, C: GraphDistance, const N: usize>(
+    graph: &'a Graph<T, D, E>,
+    from: NodeId,
+    to: NodeId,
+    cost: impl Fn(&GraphEdge<'a, D, E>) -> CostVector<C, N>,
+) -> Result<ParetoFront<'a, D, E, C, N>, NegativeDistance> {
+    let negative = |edge: &GraphEdge<'a, D, E>| cost(edge).0.iter().any(|cost| *cost < C::zero());
+    if let Some(edge) = graph.edges().find(negative) {
+        return Err(NegativeDistance(edge.id));
+    }
+
+    let mut labels = vec![Label {
+        node: from,
+        cost: CostVector::zero(),
+        previous: None,
+    }];
+    // labels are taken lexicographically, so no later label dominates a settled one
+    let mut to_visit = PriorityQueue::<usize, CostVector<C, N>, ()>::new();
+    let mut settled = vec![Vec::<usize>::new(); graph.length()];
+    to_visit.push(0, CostVector::zero(), ());
+
+    let is_covered = |settled: &[usize], labels: &[Label<C, N>], cost: &CostVector<C, N>| {
+        settled.iter().any(|&label| labels[label].cost.covers(cost))
+    };
+    while let Some((index, current_cost, ())) = to_visit.pop_by_priority() {
+        let node = labels[index].node;
+        if is_covered(&settled[node.index()], &labels, &current_cost) {
+            continue;
+        }
+        settled[node.index()].push(index);
+        if node == to {
+            continue;
+        }
+        for edge in graph.adjacent(node) {
+            let next_cost = current_cost.clone() + cost(&edge);
+            if is_covered(&settled[edge.to.index()], &labels, &next_cost)
+                || is_covered(&settled[to.index()], &labels, &next_cost)
+            {
+                continue;
+            }
+            labels.push(Label {
+                node: edge.to,
+                cost: next_cost.clone(),
+                previous: Some((index, edge.id)),
+            });
+            to_visit.push(labels.len() - 1, next_cost, ());
+        }
+    }
+
+    Ok(settled[to.index()]
+        .iter()
+        .map(|&index| {
+            let mut path = Vec::new();
+            let mut label = &labels[index];
+            while let Some((previous, edge)) = label.previous {
+                path.push(graph.edge(edge));
+                label = &labels[previous];
+            }
+            path.reverse();
+            (labels[index].cost.clone(), path)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::GraphBuilder;
+
+    use super::*;
+
+    #[test]
+    fn front_of_distance_and_hops() {
+        let mut builder = GraphBuilder::new();
+        let nodes = (0..5).map(|i| builder.add_node(i)).collect::<Vec<_>>();
+        // direct but long, two hops of medium length, four short hops
+        builder.add_edge(nodes[0], nodes[4], 10u32, ());
+        builder.add_edge(nodes[0], nodes[2], 3u32, ());
+        builder.add_edge(nodes[2], nodes[4], 4u32, ());
+        builder.add_edge(nodes[0], nodes[1], 1u32, ());
+        builder.add_edge(nodes[1], nodes[2], 1u32, ());
+        builder.add_edge(nodes[2], nodes[3], 1u32, ());
+        builder.add_edge(nodes[3], nodes[4], 1u32, ());
+        // dominated: as many hops as the medium route and longer
+        builder.add_edge(nodes[1], nodes[4], 8u32, ());
+        let graph = builder.build();
+
+        let front = pareto_paths(&graph, nodes[0], nodes[4], |edge| {
+            CostVector([*edge.distance, 1])
+        })
+        .unwrap();
+        assert_eq!(
+            front
+                .iter()
+                .map(|(cost, path)| (cost.0, path.len()))
+                .collect::<Vec<_>>(),
+            vec![([4, 4], 4), ([5, 3], 3), ([7, 2], 2), ([10, 1], 1)]
+        );
+        assert!(
+            pareto_paths(&graph, nodes[4], nodes[0], |_| CostVector([1u32]))
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            pareto_paths(&graph, nodes[0], nodes[0], |_| CostVector([1u32])),
+            Ok(vec![(CostVector([0]), vec![])])
+        );
+    }
+
+    #[test]
+    fn refuses_negative_cost() {
+        let graph = Graph::from_edges(
+            vec!["a", "b", "t"],
+            vec![(0, 2, 5u32, ()), (0, 1, 6, ()), (1, 2, 1, ())],
+        )
+        .unwrap();
+        let nodes = graph.node_ids().collect::<Vec<_>>();
+        let negative = graph.adjacent(nodes[1]).next().unwrap().id;
+        // distance, and a bonus of 10 for the edge from b
+        let cost = |edge: &GraphEdge<'_, u32, ()>| {
+            let bonus = if edge.id == negative { 10 } else { 0 };
+            CostVector([*edge.distance as i32 - bonus, 1])
+        };
+        assert_eq!(
+            pareto_paths(&graph, nodes[0], nodes[2], cost),
+            Err(NegativeDistance(negative))
+        );
+    }
+
+    #[test]
+    fn dominates() {
+        assert!(CostVector([1, 2]).dominates(&CostVector([1, 3])));
+        assert!(!CostVector([1, 2]).dominates(&CostVector([1, 2])));
+        assert!(!CostVector([1, 4]).dominates(&CostVector([2, 3])));
+        assert_eq!(CostVector([1, 4]) + CostVector([2, 3]), CostVector([3, 7]));
+    }
+}
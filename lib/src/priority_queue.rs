@@ -1,14 +1,15 @@
-use std::{cell::RefCell, collections::HashMap, hash::Hash, ops::Deref, rc::Rc};
+use std::{collections::HashMap, hash::Hash};
 
-#[derive(Debug)]
+/// Binary min-heap keyed by `T`, whose priority can be lowered in place
+#[derive(Debug, Clone)]
 pub struct PriorityQueue<T: Eq + Hash + Clone, P: Ord + Clone, X: Clone> {
-    nodes: Vec<Rc<RefCell<Node<T, P, X>>>>,
-    map: HashMap<T, Rc<RefCell<Node<T, P, X>>>>,
+    nodes: Vec<Node<T, P, X>>,
+    /// index in `nodes` of every queued `T`
+    map: HashMap<T, usize>,
 }
 
 #[derive(Debug, Clone)]
 struct Node<T: Eq + Hash + Clone, P: Ord + Clone, X: Clone> {
-    index: usize,
     data: T,
     priority: P,
     extra: X,
@@ -22,25 +23,9 @@ fn left_child_index(index: usize) -> usize {
     2 * index + 1
 }
 
-impl<T: Eq + Hash + Clone, P: Ord + Clone, X: Clone> Clone for PriorityQueue<T, P, X> {
-    fn clone(&self) -> Self {
-        let nodes = self
-            .nodes
-            .iter()
-            .map(|rc| {
-                Rc::new(RefCell::new(
-                    (rc as &dyn Deref<Target = RefCell<Node<T, P, X>>>)
-                        .deref()
-                        .borrow()
-                        .clone(),
-                ))
-            })
-            .collect::<Vec<_>>();
-        let mut map = HashMap::new();
-        for rc in nodes.iter() {
-            map.insert(rc.borrow().data.clone(), rc.clone());
-        }
-        PriorityQueue { nodes, map }
+impl<T: Eq + Hash + Clone, P: Ord + Clone, X: Clone> Default for PriorityQueue<T, P, X> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -52,92 +37,90 @@ impl<T: Eq + Hash + Clone, P: Ord + Clone, X: Clone> PriorityQueue<T, P, X> {
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Entry with the lowest priority, without removing it
+    pub fn peek(&self) -> Option<(&T, &P, &X)> {
+        self.nodes
+            .first()
+            .map(|node| (&node.data, &node.priority, &node.extra))
+    }
+
     pub fn pop_by_priority(&mut self) -> Option<(T, P, X)> {
-        if self.nodes.len() == 0 {
-            None
-        } else if self.nodes.len() == 1 {
-            let root = self.nodes.pop().unwrap();
-            let root_node = root.borrow();
-            self.map.remove(&root_node.data);
-            Some((
-                root_node.data.clone(),
-                root_node.priority.clone(),
-                root_node.extra.clone(),
-            ))
-        } else {
-            let result = {
-                let root = self.nodes[0].clone();
-                let root_node = root.borrow();
-                self.map.remove(&root_node.data);
-                Some((
-                    root_node.data.clone(),
-                    root_node.priority.clone(),
-                    root_node.extra.clone(),
-                ))
-            };
-            let last = self.nodes.pop().unwrap();
-            last.borrow_mut().index = 0;
-            self.nodes[0] = last;
-            self.heapify(0);
-            result
+        if self.nodes.is_empty() {
+            return None;
         }
+        let root = self.nodes.swap_remove(0);
+        self.map.remove(&root.data);
+        if !self.nodes.is_empty() {
+            self.map.insert(self.nodes[0].data.clone(), 0);
+            self.sift_down(0);
+        }
+        Some((root.data, root.priority, root.extra))
     }
 
+    /// Inserts `data`, or lowers its priority and replaces its extra if already queued with a
+    /// higher priority
     pub fn push(&mut self, data: T, priority: P, extra: X) {
-        if let Some(node) = self.map.get(&data) {
-            self.heapify({
-                let mut borrow = node.borrow_mut();
-                borrow.priority = priority;
-                borrow.index
-            });
+        if let Some(&index) = self.map.get(&data) {
+            let node = &mut self.nodes[index];
+            if node.priority <= priority {
+                return;
+            }
+            node.priority = priority;
+            node.extra = extra;
+            self.sift_up(index);
         } else {
-            let node = Rc::new(RefCell::new(Node {
-                index: self.nodes.len(),
-                data: data.clone(),
+            let index = self.nodes.len();
+            self.map.insert(data.clone(), index);
+            self.nodes.push(Node {
+                data,
                 priority,
                 extra,
-            }));
-            self.nodes.push(node.clone());
-            self.map.insert(data, node);
-            self.heapify(self.nodes.len() - 1);
+            });
+            self.sift_up(index);
         }
     }
 
-    fn heapify(&mut self, index: usize) {
-        if index != 0 {
+    fn swap(&mut self, a: usize, b: usize) {
+        self.nodes.swap(a, b);
+        *self.map.get_mut(&self.nodes[a].data).unwrap() = a;
+        *self.map.get_mut(&self.nodes[b].data).unwrap() = b;
+    }
+
+    fn sift_up(&mut self, mut index: usize) {
+        while index != 0 {
             let parent_index = parent_index(index);
-            if self.nodes[parent_index].borrow().priority > self.nodes[index].borrow().priority {
-                (self.nodes[parent_index], self.nodes[index]) =
-                    (self.nodes[index].clone(), self.nodes[parent_index].clone());
-                self.nodes[index].borrow_mut().index = index;
-                self.nodes[parent_index].borrow_mut().index = parent_index;
-                self.heapify(parent_index);
+            if self.nodes[parent_index].priority <= self.nodes[index].priority {
+                break;
             }
+            self.swap(parent_index, index);
+            index = parent_index;
         }
-        let left_child_index = left_child_index(index);
-        if self.nodes.len() > left_child_index {
-            if self.nodes[index].borrow().priority > self.nodes[left_child_index].borrow().priority
-            {
-                (self.nodes[index], self.nodes[left_child_index]) = (
-                    self.nodes[left_child_index].clone(),
-                    self.nodes[index].clone(),
-                );
-                self.nodes[index].borrow_mut().index = index;
-                self.nodes[left_child_index].borrow_mut().index = left_child_index;
-                self.heapify(left_child_index);
+    }
+
+    fn sift_down(&mut self, mut index: usize) {
+        loop {
+            let left_child_index = left_child_index(index);
+            let mut smallest = index;
+            for child in [left_child_index, left_child_index + 1] {
+                if child < self.nodes.len()
+                    && self.nodes[child].priority < self.nodes[smallest].priority
+                {
+                    smallest = child;
+                }
             }
-            if self.nodes.len() > left_child_index + 1
-                && self.nodes[index].borrow().priority
-                    > self.nodes[left_child_index + 1].borrow().priority
-            {
-                (self.nodes[index], self.nodes[left_child_index + 1]) = (
-                    self.nodes[left_child_index + 1].clone(),
-                    self.nodes[index].clone(),
-                );
-                self.nodes[index].borrow_mut().index = index;
-                self.nodes[left_child_index + 1].borrow_mut().index = left_child_index + 1;
-                self.heapify(left_child_index + 1);
+            if smallest == index {
+                break;
             }
+            self.swap(index, smallest);
+            index = smallest;
         }
     }
 }
@@ -184,5 +167,22 @@ mod tests {
 
         pq.push(42, 42, ());
         assert_eq!(to_vec(&pq), vec![1, 2, 42, 100]);
+
+        pq.push(42, 1000, ());
+        assert_eq!(to_vec(&pq), vec![1, 2, 42, 100]);
+    }
+
+    #[test]
+    fn keeps_extra_of_lowest_priority() {
+        let mut pq = PriorityQueue::<&str, usize, usize>::new();
+        pq.push("a", 10, 1);
+        pq.push("a", 20, 2);
+        pq.push("a", 5, 3);
+        pq.push("b", 7, 4);
+        assert_eq!(pq.peek(), Some((&"a", &5, &3)));
+        assert_eq!(pq.pop_by_priority(), Some(("a", 5, 3)));
+        assert_eq!(pq.pop_by_priority(), Some(("b", 7, 4)));
+        assert_eq!(pq.peek(), None);
+        assert_eq!(pq.pop_by_priority(), None);
     }
 }
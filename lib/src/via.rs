@@ -0,0 +1,349 @@
+use std::{error::Error, fmt};
+
+use crate::{
+    check_distances,
+    graph::{Graph, GraphDistance, GraphEdge, NodeId},
+    search, NegativeDistance, ShortestPathTree,
+};
+
+/// Most waypoints [`via_path_unordered`] accepts, as it weighs every subset of them
+pub const MAX_UNORDERED_WAYPOINTS: usize = 12;
+
+/// Reason [`via_path_unordered`] cannot search
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViaError {
+    NegativeDistance(NegativeDistance),
+    /// `got` waypoints, more than the `max` of [`MAX_UNORDERED_WAYPOINTS`]
+    TooManyWaypoints {
+        max: usize,
+        got: usize,
+    },
+}
+
+impl fmt::Display for ViaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViaError::NegativeDistance(negative) => write!(f, "{}", negative),
+            ViaError::TooManyWaypoints { max, got } => write!(
+                f,
+                "{} waypoints, at most {} can be visited in any order",
+                got, max
+            ),
+        }
+    }
+}
+
+impl Error for ViaError {}
+
+impl From<NegativeDistance> for ViaError {
+    fn from(negative: NegativeDistance) -> ViaError {
+        ViaError::NegativeDistance(negative)
+    }
+}
+
+/// Tree of shortest paths from `from` over every edge, once [`check_distances`] passed
+fn tree<T, D: GraphDistance, E>(graph: &Graph<T, D, E>, from: NodeId) -> ShortestPathTree<D> {
+    search(graph, from, None, |_| D::zero(), |_| true)
+}
+
+/// Concatenates the path from the root of each tree to the node given with it
+fn join<'a, T, D: GraphDistance, E>(
+    graph: &'a Graph<T, D, E>,
+    legs: &[(&ShortestPathTree<D>, NodeId)],
+) -> Option<Vec<GraphEdge<'a, D, E>>> {
+    let mut path = Vec::new();
+    for (tree, to) in legs {
+        path.extend(tree.path_to(graph, *to)?);
+    }
+    Some(path)
+}
+
+/// Shortest path from `from` to `to` meeting every waypoint of `via` in order, where a waypoint
+/// is met at any one of its nodes; None if some waypoint cannot be reached in turn;
+/// fails on a negative distance
+pub fn via_path<'a, T, D: GraphDistance, E>(
+    graph: &'a Graph<T, D, E>,
+    from: NodeId,
+    via: &[Vec<NodeId>],
+    to: NodeId,
+) -> Result<Option<Vec<GraphEdge<'a, D, E>>>, NegativeDistance> {
+    check_distances(graph, |_| true)?;
+    Ok(layered(graph, from, via, to))
+}
+
+/// [`via_path`] once distances are checked
+fn layered<'a, T, D: GraphDistance, E>(
+    graph: &'a Graph<T, D, E>,
+    from: NodeId,
+    via: &[Vec<NodeId>],
+    to: NodeId,
+) -> Option<Vec<GraphEdge<'a, D, E>>> {
+    let mut layers = vec![vec![from]];
+    layers.extend(via.iter().cloned());
+    layers.push(vec![to]);
+
+    // for every node of every layer, the cheapest way there with the node of the previous
+    // layer it comes from
+    let mut best = vec![vec![Some((D::zero(), 0))]];
+    let mut trees = Vec::<Vec<Option<ShortestPathTree<D>>>>::new();
+    for (previous, layer) in layers.iter().zip(layers.iter().skip(1)) {
+        let reached = best.last().unwrap();
+        let layer_trees = previous
+            .iter()
+            .zip(reached)
+            .map(|(&node, cost)| cost.as_ref().map(|_| tree(graph, node)))
+            .collect::<Vec<_>>();
+        let costs = layer
+            .iter()
+            .map(|&node| {
+                (0..previous.len())
+                    .filter_map(|index| {
+                        let (cost, _) = reached[index].as_ref()?;
+                        let distance = layer_trees[index].as_ref()?.distance(node)?;
+                        Some((cost.clone() + distance.clone(), index))
+                    })
+                    .min_by(|a, b| a.0.cmp(&b.0))
+            })
+            .collect::<Vec<_>>();
+        best.push(costs);
+        trees.push(layer_trees);
+    }
+
+    // back from `to`, the only node of the last layer
+    let mut legs = Vec::new();
+    let mut index = 0;
+    for layer in (1..layers.len()).rev() {
+        let (_, previous) = best[layer][index].as_ref()?;
+        legs.push((
+            trees[layer - 1][*previous].as_ref().unwrap(),
+            layers[layer][index],
+        ));
+        index = *previous;
+    }
+    legs.reverse();
+    join(graph, &legs)
+}
+
+/// Shortest path from `from` to `to` meeting every waypoint of `via` in whichever order is
+/// cheapest, where a waypoint is met at any one of its nodes; None if no order works;
+/// fails on a negative distance or on more than [`MAX_UNORDERED_WAYPOINTS`] waypoints
+pub fn via_path_unordered<'a, T, D: GraphDistance, E>(
+    graph: &'a Graph<T, D, E>,
+    from: NodeId,
+    via: &[Vec<NodeId>],
+    to: NodeId,
+) -> Result<Option<Vec<GraphEdge<'a, D, E>>>, ViaError> {
+    if via.len() > MAX_UNORDERED_WAYPOINTS {
+        return Err(ViaError::TooManyWaypoints {
+            max: MAX_UNORDERED_WAYPOINTS,
+            got: via.len(),
+        });
+    }
+    check_distances(graph, |_| true)?;
+    Ok(any_order(graph, from, via, to))
+}
+
+/// [`via_path_unordered`] once distances are checked
+fn any_order<'a, T, D: GraphDistance, E>(
+    graph: &'a Graph<T, D, E>,
+    from: NodeId,
+    via: &[Vec<NodeId>],
+    to: NodeId,
+) -> Option<Vec<GraphEdge<'a, D, E>>> {
+    let start = tree(graph, from);
+    if via.is_empty() {
+        return start.path_to(graph, to);
+    }
+
+    // (waypoint, node) for every node a waypoint can be met at
+    let candidates = via
+        .iter()
+        .enumerate()
+        .flat_map(|(waypoint, nodes)| nodes.iter().map(move |&node| (waypoint, node)))
+        .collect::<Vec<_>>();
+    let trees = candidates
+        .iter()
+        .map(|&(_, node)| tree(graph, node))
+        .collect::<Vec<_>>();
+
+    // best[set][candidate]: the cheapest way to meet the waypoints of `set`, the last of them
+    // at `candidate`, with the candidate before it
+    let full = (1usize << via.len()) - 1;
+    let mut best = vec![vec![None::<(D, Option<usize>)>; candidates.len()]; full + 1];
+    for (candidate, &(waypoint, node)) in candidates.iter().enumerate() {
+        best[1 << waypoint][candidate] = start.distance(node).map(|cost| (cost.clone(), None));
+    }
+    for set in 1..full {
+        for candidate in 0..candidates.len() {
+            let Some((cost, _)) = best[set][candidate].clone() else {
+                continue;
+            };
+            for (next, &(waypoint, node)) in candidates.iter().enumerate() {
+                if set & (1 << waypoint) != 0 {
+                    continue;
+                }
+                let Some(distance) = trees[candidate].distance(node) else {
+                    continue;
+                };
+                let cost = cost.clone() + distance.clone();
+                let entry = &mut best[set | (1 << waypoint)][next];
+                if entry.as_ref().is_none_or(|(best, _)| cost < *best) {
+                    *entry = Some((cost, Some(candidate)));
+                }
+            }
+        }
+    }
+
+    let (_, last) = (0..candidates.len())
+        .filter_map(|candidate| {
+            let (cost, _) = best[full][candidate].as_ref()?;
+            let distance = trees[candidate].distance(to)?;
+            Some((cost.clone() + distance.clone(), candidate))
+        })
+        .min_by(|a, b| a.0.cmp(&b.0))?;
+
+    // back from `to` through the waypoints
+    let mut legs = vec![(&trees[last], to)];
+    let (mut set, mut current) = (full, last);
+    loop {
+        let (waypoint, node) = candidates[current];
+        let previous = best[set][current].as_ref().unwrap().1;
+        set &= !(1 << waypoint);
+        match previous {
+            Some(previous) => {
+                legs.push((&trees[previous], node));
+                current = previous;
+            }
+            None => {
+                legs.push((&start, node));
+                break;
+            }
+        }
+    }
+    legs.reverse();
+    join(graph, &legs)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::GraphBuilder;
+
+    use super::*;
+
+    fn visited<D, E>(from: NodeId, path: &[GraphEdge<'_, D, E>]) -> Vec<usize> {
+        std::iter::once(from)
+            .chain(path.iter().map(|edge| edge.to))
+            .map(|node| node.index())
+            .collect()
+    }
+
+    #[test]
+    fn waypoints() {
+        // a line 0 - 1 - 2 - 3 - 4 in both directions, with a shortcut 0 -> 4
+        // and a siding 5 off node 3
+        let mut builder = GraphBuilder::new();
+        let nodes = (0..6).map(|i| builder.add_node(i)).collect::<Vec<_>>();
+        for i in 0..4 {
+            builder.add_edge(nodes[i], nodes[i + 1], 2u32, ());
+            builder.add_edge(nodes[i + 1], nodes[i], 2u32, ());
+        }
+        builder.add_edge(nodes[0], nodes[4], 1u32, ());
+        builder.add_edge(nodes[3], nodes[5], 1u32, ());
+        let graph = builder.build();
+
+        let path = via_path(&graph, nodes[0], &[], nodes[4]).unwrap().unwrap();
+        assert_eq!(visited(nodes[0], &path), vec![0, 4]);
+        let path = via_path(&graph, nodes[0], &[vec![nodes[2]]], nodes[4])
+            .unwrap()
+            .unwrap();
+        assert_eq!(visited(nodes[0], &path), vec![0, 1, 2, 3, 4]);
+
+        // in the given order, back and forth
+        let via = [vec![nodes[3]], vec![nodes[1]]];
+        let path = via_path(&graph, nodes[0], &via, nodes[4]).unwrap().unwrap();
+        assert_eq!(visited(nodes[0], &path), vec![0, 4, 3, 2, 1, 0, 4]);
+        let path = via_path_unordered(&graph, nodes[0], &via, nodes[4])
+            .unwrap()
+            .unwrap();
+        assert_eq!(visited(nodes[0], &path), vec![0, 1, 2, 3, 4]);
+
+        // the nearest node of a waypoint is not always the best one
+        let via = [vec![nodes[1], nodes[3]]];
+        let path = via_path(&graph, nodes[0], &via, nodes[3]).unwrap().unwrap();
+        assert_eq!(visited(nodes[0], &path), vec![0, 4, 3]);
+        let path = via_path_unordered(&graph, nodes[0], &via, nodes[3])
+            .unwrap()
+            .unwrap();
+        assert_eq!(visited(nodes[0], &path), vec![0, 4, 3]);
+
+        // nothing leaves the siding
+        let via = [vec![nodes[5]], vec![nodes[1]]];
+        assert!(via_path(&graph, nodes[0], &via, nodes[4])
+            .unwrap()
+            .is_none());
+        assert!(via_path_unordered(&graph, nodes[0], &via, nodes[4])
+            .unwrap()
+            .is_none());
+        assert!(via_path_unordered(&graph, nodes[0], &via, nodes[5])
+            .unwrap()
+            .is_some());
+        assert!(via_path(&graph, nodes[0], &[vec![]], nodes[4])
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn unordered_matches_every_order() {
+        // a 4 x 4 grid with uneven distances
+        let mut builder = GraphBuilder::new();
+        let nodes = (0..16).map(|i| builder.add_node(i)).collect::<Vec<_>>();
+        for i in 0..16 {
+            for j in [i + 1, i + 4] {
+                if j < 16 && (j == i + 4 || j % 4 != 0) {
+                    let distance = (i * 7 + j * 3) as u32 % 5 + 1;
+                    builder.add_edge(nodes[i], nodes[j], distance, ());
+                    builder.add_edge(nodes[j], nodes[i], distance, ());
+                }
+            }
+        }
+        let graph = builder.build();
+        let cost = |path: &[GraphEdge<'_, u32, ()>]| path.iter().map(|e| *e.distance).sum::<u32>();
+
+        let via = [vec![nodes[5]], vec![nodes[14], nodes[3]], vec![nodes[8]]];
+        let mut orders = vec![vec![0, 1, 2]];
+        for _ in 0..5 {
+            let mut next = orders.last().unwrap().clone();
+            // every permutation of three, by adjacent swaps
+            next.swap(orders.len() % 2, orders.len() % 2 + 1);
+            orders.push(next);
+        }
+        let best = orders
+            .iter()
+            .map(|order| {
+                let via = order.iter().map(|&i| via[i].clone()).collect::<Vec<_>>();
+                cost(
+                    &via_path(&graph, nodes[0], &via, nodes[15])
+                        .unwrap()
+                        .unwrap(),
+                )
+            })
+            .min()
+            .unwrap();
+        let path = via_path_unordered(&graph, nodes[0], &via, nodes[15])
+            .unwrap()
+            .unwrap();
+        assert_eq!(cost(&path), best);
+        let visits = visited(nodes[0], &path);
+        assert!(visits.contains(&5) && visits.contains(&8));
+        assert!(visits.contains(&14) || visits.contains(&3));
+
+        let too_many = vec![vec![nodes[5]]; MAX_UNORDERED_WAYPOINTS + 1];
+        assert_eq!(
+            via_path_unordered(&graph, nodes[0], &too_many, nodes[15]),
+            Err(ViaError::TooManyWaypoints {
+                max: MAX_UNORDERED_WAYPOINTS,
+                got: MAX_UNORDERED_WAYPOINTS + 1
+            })
+        );
+    }
+}